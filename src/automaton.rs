@@ -0,0 +1,439 @@
+//! A cellular-automaton engine over [`Grid`]s.
+//!
+//! An [`Automaton`] keeps two buffers and swaps them after every generation,
+//! so rules always read a consistent snapshot of the previous generation.
+//! Rules come in two shapes:
+//!
+//! * per-cell rules, which compute each cell's next value from its current
+//!   value and its neighbours;
+//! * propose/resolve rules, where every cell may propose moving its contents
+//!   to another location, and contested targets are then resolved before any
+//!   move is applied.
+//!
+//! Simple per-cell rules over character grids can also be written as text and
+//! parsed into a [`RuleSet`]; see its `FromStr` impl for the format.
+
+use std::{
+    collections::{HashMap, HashSet},
+    error::Error,
+    str::FromStr,
+};
+
+use crate::grid::{Direction4, Direction8, Grid, Location, Vector};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Neighborhood {
+    /// The four orthogonal neighbours.
+    VonNeumann,
+    /// The four orthogonal and four diagonal neighbours.
+    Moore,
+}
+
+pub struct Automaton<T> {
+    current: Grid<T>,
+    next: Grid<T>,
+    neighborhood: Neighborhood,
+    generation: usize,
+}
+
+/// A parsed text rule set for character grids.
+#[derive(Debug, PartialEq)]
+pub struct RuleSet {
+    pub neighborhood: Neighborhood,
+    counting: char,
+    transitions: Vec<Transition>,
+}
+
+#[derive(Debug, PartialEq)]
+struct Transition {
+    from: char,
+    to: char,
+    condition: Condition,
+}
+
+#[derive(Debug, PartialEq)]
+enum Condition {
+    Always,
+    If(Vec<usize>),
+    Unless(Vec<usize>),
+}
+
+const VON_NEUMANN: [Vector; 4] = [
+    Direction4::Up.vector(),
+    Direction4::Right.vector(),
+    Direction4::Down.vector(),
+    Direction4::Left.vector(),
+];
+
+const MOORE: [Vector; 8] = [
+    Direction8::Up.vector(),
+    Direction8::UpRight.vector(),
+    Direction8::Right.vector(),
+    Direction8::DownRight.vector(),
+    Direction8::Down.vector(),
+    Direction8::DownLeft.vector(),
+    Direction8::Left.vector(),
+    Direction8::UpLeft.vector(),
+];
+
+impl Neighborhood {
+    /// The neighbour offsets, clockwise from straight up.
+    pub fn offsets(&self) -> &'static [Vector] {
+        match self {
+            Neighborhood::VonNeumann => &VON_NEUMANN,
+            Neighborhood::Moore => &MOORE,
+        }
+    }
+}
+
+impl<T: PartialEq + Default + Clone> Automaton<T> {
+    pub fn new(grid: Grid<T>, neighborhood: Neighborhood) -> Self {
+        Self {
+            next: grid.clone(),
+            current: grid,
+            neighborhood,
+            generation: 0,
+        }
+    }
+
+    pub fn grid(&self) -> &Grid<T> {
+        &self.current
+    }
+
+    #[allow(dead_code)]
+    pub fn generation(&self) -> usize {
+        self.generation
+    }
+
+    /// Replaces `out` with the neighbours of `loc` in `grid`, in the order
+    /// given by [`Neighborhood::offsets`]. Neighbours outside the grid are
+    /// `None`. Steps reuse one buffer rather than allocating for every cell.
+    fn neighbors<'a>(
+        grid: &'a Grid<T>,
+        neighborhood: Neighborhood,
+        loc: Location,
+        out: &mut Vec<Option<&'a T>>,
+    ) {
+        out.clear();
+        out.extend(
+            neighborhood
+                .offsets()
+                .iter()
+                .map(|v| grid.offset(loc, *v).and_then(|l| grid.get(l))),
+        );
+    }
+
+    /// Advances one generation with a per-cell rule, returning how many cells
+    /// changed.
+    pub fn step(&mut self, rule: impl Fn(&T, &[Option<&T>]) -> T) -> usize {
+        let mut changed = 0;
+        let mut neighbors = Vec::with_capacity(8);
+        for loc in self.current.locations() {
+            let cell = self.current.get(loc).unwrap();
+            Self::neighbors(&self.current, self.neighborhood, loc, &mut neighbors);
+            let value = rule(cell, &neighbors);
+            if value != *cell {
+                changed += 1;
+            }
+            self.next.set(loc, value);
+        }
+
+        self.swap();
+        changed
+    }
+
+    /// Advances one generation in two phases. First, `propose` may ask to move
+    /// each cell's contents to another location. Then `resolve` is called for
+    /// every target with the cells that want it, and picks which one (if any)
+    /// may move. Cells that move leave `vacated` behind. A move into a cell
+    /// that isn't `vacated` only happens if that cell's contents move away
+    /// too, so nothing is ever overwritten. Returns how many cells moved.
+    #[allow(dead_code)]
+    pub fn step_two_phase(
+        &mut self,
+        propose: impl Fn(Location, &T, &[Option<&T>]) -> Option<Location>,
+        resolve: impl Fn(Location, &[Location]) -> Option<Location>,
+        vacated: T,
+    ) -> usize {
+        let mut proposals: HashMap<Location, Vec<Location>> = HashMap::new();
+        let mut neighbors = Vec::with_capacity(8);
+        for loc in self.current.locations() {
+            let cell = self.current.get(loc).unwrap();
+            Self::neighbors(&self.current, self.neighborhood, loc, &mut neighbors);
+            if let Some(target) = propose(loc, cell, &neighbors) {
+                if target != loc && self.current.contains(target) {
+                    proposals.entry(target).or_default().push(loc);
+                }
+            }
+        }
+
+        // source by target
+        let mut winners: HashMap<Location, Location> = proposals
+            .into_iter()
+            .filter_map(|(target, mut from)| {
+                from.sort();
+                resolve(target, &from).map(|source| (target, source))
+            })
+            .collect();
+        // A cell that stays put blocks the move into it, which may keep that
+        // mover where it is in turn, so repeat until nothing else is blocked.
+        loop {
+            let leaving: HashSet<Location> = winners.values().copied().collect();
+            let blocked: Vec<Location> = winners
+                .keys()
+                .filter(|t| *self.current.get(**t).unwrap() != vacated && !leaving.contains(t))
+                .copied()
+                .collect();
+            if blocked.is_empty() {
+                break;
+            }
+            for target in blocked {
+                winners.remove(&target);
+            }
+        }
+        let moves: Vec<(Location, Location)> = winners
+            .into_iter()
+            .map(|(target, source)| (source, target))
+            .collect();
+
+        for loc in self.current.locations() {
+            self.next.set(loc, self.current.get(loc).unwrap().clone());
+        }
+        for (source, _) in &moves {
+            self.next.set(*source, vacated.clone());
+        }
+        for (source, target) in &moves {
+            self.next
+                .set(*target, self.current.get(*source).unwrap().clone());
+        }
+
+        self.swap();
+        moves.len()
+    }
+
+    /// Steps with `rule` until a generation changes nothing, and returns the
+    /// number of that generation. Gives up with `None` after `max_steps`
+    /// generations, e.g. if the pattern oscillates.
+    #[allow(dead_code)]
+    pub fn run_until_stable(
+        &mut self,
+        rule: impl Fn(&T, &[Option<&T>]) -> T,
+        max_steps: usize,
+    ) -> Option<usize> {
+        for _ in 0..max_steps {
+            if self.step(&rule) == 0 {
+                return Some(self.generation);
+            }
+        }
+        None
+    }
+
+    fn swap(&mut self) {
+        std::mem::swap(&mut self.current, &mut self.next);
+        self.generation += 1;
+    }
+}
+
+impl RuleSet {
+    /// The next value of `cell`, given its neighbours. The first transition
+    /// that starts from the cell's character and whose condition holds wins;
+    /// if none does, the cell keeps its value.
+    pub fn apply(&self, cell: &char, neighbors: &[Option<&char>]) -> char {
+        let count = neighbors
+            .iter()
+            .filter(|n| **n == Some(&self.counting))
+            .count();
+
+        self.transitions
+            .iter()
+            .find(|t| {
+                t.from == *cell
+                    && match &t.condition {
+                        Condition::Always => true,
+                        Condition::If(counts) => counts.contains(&count),
+                        Condition::Unless(counts) => !counts.contains(&count),
+                    }
+            })
+            .map_or(*cell, |t| t.to)
+    }
+}
+
+/// Parses a rule set such as:
+///
+/// ```text
+/// // Conway's Game of Life
+/// neighborhood moore
+/// counting #
+/// . -> # if 3
+/// # -> . unless 2-3
+/// ```
+///
+/// Lines starting with `//` are comments. `neighborhood` is `moore` (the
+/// default) or `vonneumann`, and `counting` names the character that is
+/// counted among each cell's neighbours. Each transition is `<from> -> <to>`,
+/// optionally followed by `if` or `unless` and a comma separated list of
+/// counts or ranges of counts.
+impl FromStr for RuleSet {
+    type Err = Box<dyn Error>;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut neighborhood = None;
+        let mut counting = None;
+        let mut transitions = Vec::new();
+
+        for (n, line) in s.lines().enumerate().map(|(n, l)| (n + 1, l.trim())) {
+            if line.is_empty() || line.starts_with("//") {
+                continue;
+            }
+
+            let words: Vec<&str> = line.split_whitespace().collect();
+            match words.as_slice() {
+                ["neighborhood", "moore"] => neighborhood = Some(Neighborhood::Moore),
+                ["neighborhood", "vonneumann"] => neighborhood = Some(Neighborhood::VonNeumann),
+                ["counting", c] => counting = Some(single_char(c, n)?),
+                [from, "->", to, rest @ ..] => transitions.push(Transition {
+                    from: single_char(from, n)?,
+                    to: single_char(to, n)?,
+                    condition: match rest {
+                        [] => Condition::Always,
+                        ["if", counts] => Condition::If(parse_counts(counts, n)?),
+                        ["unless", counts] => Condition::Unless(parse_counts(counts, n)?),
+                        _ => {
+                            return Err(
+                                format!("line {n}: bad condition '{}'", rest.join(" ")).into()
+                            )
+                        }
+                    },
+                }),
+                _ => return Err(format!("line {n}: unrecognised rule '{line}'").into()),
+            }
+        }
+
+        Ok(Self {
+            neighborhood: neighborhood.unwrap_or(Neighborhood::Moore),
+            counting: counting.ok_or("rule set is missing a 'counting' line")?,
+            transitions,
+        })
+    }
+}
+
+fn single_char(s: &str, line: usize) -> Result<char, Box<dyn Error>> {
+    let mut chars = s.chars();
+    match (chars.next(), chars.next()) {
+        (Some(c), None) => Ok(c),
+        _ => Err(format!("line {line}: expected a single character, found '{s}'").into()),
+    }
+}
+
+fn parse_counts(s: &str, line: usize) -> Result<Vec<usize>, Box<dyn Error>> {
+    let mut counts = Vec::new();
+    for part in s.split(',') {
+        let bad = || format!("line {line}: bad count '{part}'");
+        match part.split_once('-') {
+            Some((lo, hi)) => {
+                let lo: usize = lo.parse().map_err(|_| bad())?;
+                let hi: usize = hi.parse().map_err(|_| bad())?;
+                counts.extend(lo..=hi);
+            }
+            None => counts.push(part.parse().map_err(|_| bad())?),
+        }
+    }
+    Ok(counts)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const LIFE: &str = "// Conway's Game of Life
+neighborhood moore
+counting #
+. -> # if 3
+# -> . unless 2-3
+";
+
+    fn char_grid(s: &str) -> Grid<char> {
+        Grid::from_vec(s.lines().map(|l| l.chars().collect()).collect())
+    }
+
+    #[test]
+    fn test_parse_rules() {
+        let rules: RuleSet = LIFE.parse().unwrap();
+        assert_eq!(rules.neighborhood, Neighborhood::Moore);
+        assert_eq!(rules.transitions.len(), 2);
+        assert_eq!(
+            rules.transitions[1].condition,
+            Condition::Unless(vec![2, 3])
+        );
+
+        assert!("counting ##".parse::<RuleSet>().is_err());
+        assert!("counting #\n. -> # when 3".parse::<RuleSet>().is_err());
+        assert!(". -> #".parse::<RuleSet>().is_err());
+    }
+
+    #[test]
+    fn test_blinker_oscillates() {
+        let rules: RuleSet = LIFE.parse().unwrap();
+        let start = char_grid(".....\n..#..\n..#..\n..#..\n.....");
+        let mut automaton = Automaton::new(start.clone(), rules.neighborhood);
+
+        assert_eq!(automaton.step(|c, n| rules.apply(c, n)), 4);
+        assert_eq!(
+            automaton.grid().to_string(),
+            ".....\n.....\n.###.\n.....\n.....\n"
+        );
+        automaton.step(|c, n| rules.apply(c, n));
+        assert_eq!(automaton.grid().rows(), start.rows());
+        assert_eq!(
+            automaton.run_until_stable(|c, n| rules.apply(c, n), 10),
+            None
+        );
+    }
+
+    #[test]
+    fn test_block_is_stable() {
+        let rules: RuleSet = LIFE.parse().unwrap();
+        let mut automaton = Automaton::new(char_grid("....\n.##.\n.##.\n...."), rules.neighborhood);
+        assert_eq!(
+            automaton.run_until_stable(|c, n| rules.apply(c, n), 10),
+            Some(1)
+        );
+    }
+
+    #[test]
+    fn test_two_phase_collisions() {
+        // Both 'o's on the top row want the middle cell, so neither moves; the
+        // one on the bottom row moves right unopposed.
+        let grid = char_grid("o.o\no..");
+        let mut automaton = Automaton::new(grid, Neighborhood::VonNeumann);
+        let moved = automaton.step_two_phase(
+            |loc, c, n| match (*c, loc.row) {
+                ('o', 0) => Some(Location::new(0, 1)),
+                ('o', _) if n[1] == Some(&'.') => Some(loc.right()),
+                _ => None,
+            },
+            |_, from| (from.len() == 1).then(|| from[0]),
+            '.',
+        );
+
+        assert_eq!(moved, 1);
+        assert_eq!(automaton.grid().to_string(), "o.o\n.o.\n");
+    }
+
+    #[test]
+    fn test_two_phase_occupied_target() {
+        // Every 'o' wants the cell to its right. The '#' never moves, so the
+        // 'o' next to it stays, and so does the one queued up behind it; the
+        // lone 'o' on the bottom row moves into the cell its neighbour leaves.
+        let grid = char_grid("oo#.\noo..");
+        let mut automaton = Automaton::new(grid, Neighborhood::VonNeumann);
+        let moved = automaton.step_two_phase(
+            |loc, c, _| (*c == 'o').then(|| loc.right()),
+            |_, from| from.first().copied(),
+            '.',
+        );
+
+        assert_eq!(moved, 2);
+        assert_eq!(automaton.grid().to_string(), "oo#.\n.oo.\n");
+    }
+}
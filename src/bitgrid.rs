@@ -0,0 +1,376 @@
+//! A dense grid of booleans packed into machine words, for simulations that
+//! need fast whole-row operations and collision tests.
+//!
+//! Cells are addressed with [`Location`], using the same axis convention as
+//! [`crate::grid`]. Within a row, column `c` is stored in bit `c % 64` of word
+//! `c / 64`.
+
+use std::{
+    fmt::{Display, Write},
+    ops::{BitAnd, BitAndAssign, BitOr, BitOrAssign},
+};
+
+use crate::grid::Location;
+
+const WORD_BITS: usize = u64::BITS as usize;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct BitGrid {
+    width: usize,
+    height: usize,
+    words_per_row: usize,
+    words: Vec<u64>,
+}
+
+impl BitGrid {
+    pub fn new(width: usize, height: usize) -> Self {
+        let words_per_row = width.div_ceil(WORD_BITS);
+        Self {
+            width,
+            height,
+            words_per_row,
+            words: vec![0; words_per_row * height],
+        }
+    }
+
+    /// Builds a grid from rows of `#` (set) and any other character (clear).
+    /// The grid is as wide as the longest row.
+    pub fn from_rows<'a>(rows: impl IntoIterator<Item = &'a str>) -> Self {
+        let rows: Vec<&str> = rows.into_iter().collect();
+        let width = rows.iter().map(|r| r.chars().count()).max().unwrap_or(0);
+        let mut grid = Self::new(width, rows.len());
+        for (row, line) in rows.iter().enumerate() {
+            for (col, c) in line.chars().enumerate() {
+                grid.set(Location::new(row, col), c == '#');
+            }
+        }
+        grid
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn get(&self, loc: Location) -> bool {
+        loc.row < self.height
+            && loc.col < self.width
+            && self.row_words(loc.row)[loc.col / WORD_BITS] & (1 << (loc.col % WORD_BITS)) != 0
+    }
+
+    pub fn set(&mut self, loc: Location, value: bool) {
+        assert!(
+            loc.row < self.height && loc.col < self.width,
+            "{loc:?} is outside a {}x{} grid",
+            self.width,
+            self.height
+        );
+        let word = &mut self.row_words_mut(loc.row)[loc.col / WORD_BITS];
+        let bit = 1 << (loc.col % WORD_BITS);
+        if value {
+            *word |= bit;
+        } else {
+            *word &= !bit;
+        }
+    }
+
+    /// Grows or shrinks the grid to `height` rows; new rows are clear.
+    pub fn resize_rows(&mut self, height: usize) {
+        self.words.resize(height * self.words_per_row, 0);
+        self.height = height;
+    }
+
+    pub fn count_ones(&self) -> usize {
+        self.words.iter().map(|w| w.count_ones() as usize).sum()
+    }
+
+    pub fn row_any(&self, row: usize) -> bool {
+        self.row_words(row).iter().any(|w| *w != 0)
+    }
+
+    /// The set cells, row by row, found a word at a time.
+    pub fn ones(&self) -> impl Iterator<Item = Location> + '_ {
+        (0..self.height).flat_map(move |row| {
+            self.row_words(row)
+                .iter()
+                .enumerate()
+                .flat_map(move |(i, &word)| {
+                    let mut rest = word;
+                    std::iter::from_fn(move || {
+                        (rest != 0).then(|| {
+                            let bit = rest.trailing_zeros() as usize;
+                            rest &= rest - 1;
+                            Location::new(row, i * WORD_BITS + bit)
+                        })
+                    })
+                })
+        })
+    }
+
+    /// Moves every cell `n` columns to the left. Cells shifted past the edge
+    /// are dropped.
+    #[allow(dead_code)]
+    pub fn shift_left(&mut self, n: usize) {
+        let (skip, bits) = (n / WORD_BITS, n % WORD_BITS);
+        for row in 0..self.height {
+            let words = self.row_words_mut(row);
+            for w in 0..words.len() {
+                let lo = words.get(w + skip).copied().unwrap_or(0);
+                let hi = words.get(w + skip + 1).copied().unwrap_or(0);
+                words[w] = if bits == 0 {
+                    lo
+                } else {
+                    (lo >> bits) | (hi << (WORD_BITS - bits))
+                };
+            }
+        }
+    }
+
+    /// Moves every cell `n` columns to the right. Cells shifted past the edge
+    /// are dropped.
+    #[allow(dead_code)]
+    pub fn shift_right(&mut self, n: usize) {
+        let (skip, bits) = (n / WORD_BITS, n % WORD_BITS);
+        for row in 0..self.height {
+            let words = self.row_words_mut(row);
+            for w in (0..words.len()).rev() {
+                let hi = w.checked_sub(skip).map_or(0, |i| words[i]);
+                let lo = w.checked_sub(skip + 1).map_or(0, |i| words[i]);
+                words[w] = if bits == 0 {
+                    hi
+                } else {
+                    (hi << bits) | (lo >> (WORD_BITS - bits))
+                };
+            }
+        }
+        self.clear_padding();
+    }
+
+    /// Tests whether `mask`, placed with its top-left cell at `at`, overlaps a
+    /// set cell of this grid or has a set cell that falls outside it.
+    pub fn collides(&self, mask: &BitGrid, at: Location) -> bool {
+        self.placed_rows(mask, at).any(|(row, words)| {
+            if row < self.height {
+                self.overflows(&words)
+                    || words
+                        .iter()
+                        .zip(self.row_words(row))
+                        .any(|(m, w)| m & w != 0)
+            } else {
+                words.iter().any(|m| *m != 0)
+            }
+        })
+    }
+
+    /// Sets every cell covered by a set cell of `mask` placed at `at`. Parts of
+    /// the mask outside this grid are ignored.
+    pub fn stamp(&mut self, mask: &BitGrid, at: Location) {
+        let last_word_mask = self.last_word_mask();
+        let placed: Vec<(usize, Vec<u64>)> = self
+            .placed_rows(mask, at)
+            .filter(|(row, _)| *row < self.height)
+            .collect();
+        for (row, words) in placed {
+            let row_words = self.row_words_mut(row);
+            for (w, m) in row_words.iter_mut().zip(words) {
+                *w |= m;
+            }
+            if let Some(last) = row_words.last_mut() {
+                *last &= last_word_mask;
+            }
+        }
+    }
+
+    /// Lays each row of `mask` out in this grid's word layout, shifted right by
+    /// `at.col`, alongside the row of this grid it lands on. Each row gets one
+    /// extra trailing word to catch bits that fall past the right edge.
+    fn placed_rows<'a>(
+        &'a self,
+        mask: &'a BitGrid,
+        at: Location,
+    ) -> impl Iterator<Item = (usize, Vec<u64>)> + 'a {
+        let (skip, bits) = (at.col / WORD_BITS, at.col % WORD_BITS);
+        (0..mask.height).map(move |r| {
+            let mut words = vec![0; self.words_per_row + 1];
+            for (i, m) in mask.row_words(r).iter().enumerate() {
+                let target = (i + skip).min(self.words_per_row);
+                words[target] |= m << bits;
+                if bits > 0 {
+                    words[(target + 1).min(self.words_per_row)] |= m >> (WORD_BITS - bits);
+                }
+            }
+            (at.row + r, words)
+        })
+    }
+
+    fn overflows(&self, placed: &[u64]) -> bool {
+        placed[self.words_per_row] != 0
+            || (self.words_per_row > 0
+                && placed[self.words_per_row - 1] & !self.last_word_mask() != 0)
+    }
+
+    fn last_word_mask(&self) -> u64 {
+        match self.width % WORD_BITS {
+            0 => u64::MAX,
+            n => (1 << n) - 1,
+        }
+    }
+
+    fn clear_padding(&mut self) {
+        let mask = self.last_word_mask();
+        for row in 0..self.height {
+            if let Some(last) = self.row_words_mut(row).last_mut() {
+                *last &= mask;
+            }
+        }
+    }
+
+    /// The words holding `row`; bits past the grid's width are always clear.
+    pub fn row_words(&self, row: usize) -> &[u64] {
+        &self.words[row * self.words_per_row..(row + 1) * self.words_per_row]
+    }
+
+    /// The words holding `row`, for whole-row updates. Callers must leave the
+    /// bits past the grid's width clear.
+    pub fn row_words_mut(&mut self, row: usize) -> &mut [u64] {
+        &mut self.words[row * self.words_per_row..(row + 1) * self.words_per_row]
+    }
+
+    fn combine(&mut self, other: &BitGrid, f: impl Fn(u64, u64) -> u64) {
+        assert!(
+            self.width == other.width && self.height == other.height,
+            "cannot combine a {}x{} grid with a {}x{} grid",
+            self.width,
+            self.height,
+            other.width,
+            other.height
+        );
+        for (w, o) in self.words.iter_mut().zip(&other.words) {
+            *w = f(*w, *o);
+        }
+    }
+}
+
+impl BitAndAssign<&BitGrid> for BitGrid {
+    fn bitand_assign(&mut self, other: &BitGrid) {
+        self.combine(other, |a, b| a & b)
+    }
+}
+
+impl BitOrAssign<&BitGrid> for BitGrid {
+    fn bitor_assign(&mut self, other: &BitGrid) {
+        self.combine(other, |a, b| a | b)
+    }
+}
+
+impl BitAnd<&BitGrid> for &BitGrid {
+    type Output = BitGrid;
+
+    fn bitand(self, other: &BitGrid) -> BitGrid {
+        let mut result = self.clone();
+        result &= other;
+        result
+    }
+}
+
+impl BitOr<&BitGrid> for &BitGrid {
+    type Output = BitGrid;
+
+    fn bitor(self, other: &BitGrid) -> BitGrid {
+        let mut result = self.clone();
+        result |= other;
+        result
+    }
+}
+
+impl Display for BitGrid {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for row in 0..self.height {
+            for col in 0..self.width {
+                f.write_char(if self.get(Location::new(row, col)) {
+                    '#'
+                } else {
+                    '.'
+                })?;
+            }
+            f.write_char('\n')?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_get_set_count() {
+        let mut grid = BitGrid::new(70, 2);
+        grid.set(Location::new(1, 65), true);
+        grid.set(Location::new(0, 3), true);
+        assert!(grid.get(Location::new(1, 65)));
+        assert!(!grid.get(Location::new(1, 64)));
+        assert!(!grid.get(Location::new(5, 5)));
+        assert_eq!(grid.count_ones(), 2);
+
+        grid.set(Location::new(0, 3), false);
+        assert_eq!(grid.count_ones(), 1);
+        assert!(!grid.row_any(0));
+    }
+
+    #[test]
+    fn test_ones() {
+        let mut grid = BitGrid::new(130, 3);
+        for (row, col) in [(0, 0), (0, 63), (0, 64), (2, 5), (2, 129)] {
+            grid.set(Location::new(row, col), true);
+        }
+        let ones: Vec<(usize, usize)> = grid.ones().map(|l| (l.row, l.col)).collect();
+        assert_eq!(ones, vec![(0, 0), (0, 63), (0, 64), (2, 5), (2, 129)]);
+        assert_eq!(BitGrid::new(10, 2).ones().count(), 0);
+    }
+
+    #[test]
+    fn test_shift() {
+        let mut grid = BitGrid::from_rows(["#..#....", "........"]);
+        grid.shift_right(2);
+        assert_eq!(grid.to_string(), "..#..#..\n........\n");
+        grid.shift_right(3);
+        assert_eq!(grid.to_string(), ".....#..\n........\n");
+        grid.shift_left(5);
+        assert_eq!(grid.to_string(), "#.......\n........\n");
+
+        let mut wide = BitGrid::new(130, 1);
+        wide.set(Location::new(0, 60), true);
+        wide.shift_right(66);
+        assert!(wide.get(Location::new(0, 126)));
+        wide.shift_right(4);
+        assert_eq!(wide.count_ones(), 0);
+    }
+
+    #[test]
+    fn test_and_or() {
+        let a = BitGrid::from_rows(["##..", "#..."]);
+        let b = BitGrid::from_rows([".##.", "#..#"]);
+        assert_eq!((&a & &b).to_string(), ".#..\n#...\n");
+        assert_eq!((&a | &b).to_string(), "###.\n#..#\n");
+    }
+
+    #[test]
+    fn test_collides_and_stamp() {
+        let mut board = BitGrid::from_rows([".......", ".......", "..#...."]);
+        let plus = BitGrid::from_rows([".#.", "###", ".#."]);
+
+        assert!(!board.collides(&plus, Location::new(0, 0)));
+        assert!(board.collides(&plus, Location::new(0, 1)));
+        assert!(board.collides(&plus, Location::new(0, 5)));
+        assert!(board.collides(&plus, Location::new(1, 3)));
+        assert!(!board.collides(&plus, Location::new(0, 4)));
+
+        board.stamp(&plus, Location::new(0, 4));
+        assert_eq!(board.to_string(), ".....#.\n....###\n..#..#.\n");
+    }
+}
@@ -0,0 +1,198 @@
+//! Cycle detection for long-running simulations, and extrapolation of
+//! quantities that grow by a fixed amount every time the cycle repeats.
+//!
+//! States are numbered by step: state 0 is the initial state, state `i` is the
+//! state after `i` steps. A [`Cycle`] starting at `start` with length `length`
+//! means state `start + length` is the same as state `start`.
+
+use std::{collections::HashMap, hash::Hash};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cycle {
+    pub start: usize,
+    pub length: usize,
+}
+
+/// Detects a cycle in states observed one at a time, by remembering when each
+/// state was first seen. Suits simulations that are stepped in place and can
+/// produce a compact snapshot of their state.
+pub struct CycleDetector<S> {
+    seen: HashMap<S, usize>,
+}
+
+impl Cycle {
+    /// How much a quantity grows over one pass of the cycle, given its value
+    /// at every step up to at least `start + length`.
+    pub fn delta(&self, values: &[i64]) -> i64 {
+        values[self.start + self.length] - values[self.start]
+    }
+
+    /// Predicts a quantity's value at `step`, given its value at every step up
+    /// to at least `start + length`.
+    pub fn extrapolate(&self, values: &[i64], step: usize) -> i64 {
+        if step < values.len() {
+            return values[step];
+        }
+
+        let cycles = (step - self.start) / self.length;
+        let offset = (step - self.start) % self.length;
+        values[self.start + offset] + cycles as i64 * self.delta(values)
+    }
+}
+
+impl<S: Hash + Eq> CycleDetector<S> {
+    pub fn new() -> Self {
+        Self {
+            seen: HashMap::new(),
+        }
+    }
+
+    /// Records the next state, returning the cycle once a state repeats.
+    pub fn observe(&mut self, state: S) -> Option<Cycle> {
+        let step = self.seen.len();
+        match self.seen.get(&state) {
+            Some(&start) => Some(Cycle {
+                start,
+                length: step - start,
+            }),
+            None => {
+                self.seen.insert(state, step);
+                None
+            }
+        }
+    }
+}
+
+/// Floyd's tortoise-and-hare detection for a pure step function. Uses constant
+/// memory but calls `step` roughly three times as often as there are states
+/// before the cycle closes.
+#[allow(dead_code)]
+pub fn floyd<S: PartialEq + Clone>(initial: S, step: impl Fn(&S) -> S) -> Cycle {
+    let mut tortoise = step(&initial);
+    let mut hare = step(&tortoise);
+    while tortoise != hare {
+        tortoise = step(&tortoise);
+        hare = step(&step(&hare));
+    }
+
+    let mut start = 0;
+    tortoise = initial;
+    while tortoise != hare {
+        tortoise = step(&tortoise);
+        hare = step(&hare);
+        start += 1;
+    }
+
+    let mut length = 1;
+    hare = step(&tortoise);
+    while tortoise != hare {
+        hare = step(&hare);
+        length += 1;
+    }
+
+    Cycle { start, length }
+}
+
+/// Brent's detection for a pure step function. Uses constant memory and
+/// usually fewer calls to `step` than [`floyd`].
+#[allow(dead_code)]
+pub fn brent<S: PartialEq + Clone>(initial: S, step: impl Fn(&S) -> S) -> Cycle {
+    let mut power = 1;
+    let mut length = 1;
+    let mut tortoise = initial.clone();
+    let mut hare = step(&initial);
+    while tortoise != hare {
+        if power == length {
+            tortoise = hare.clone();
+            power *= 2;
+            length = 0;
+        }
+        hare = step(&hare);
+        length += 1;
+    }
+
+    tortoise = initial.clone();
+    hare = initial;
+    for _ in 0..length {
+        hare = step(&hare);
+    }
+
+    let mut start = 0;
+    while tortoise != hare {
+        tortoise = step(&tortoise);
+        hare = step(&hare);
+        start += 1;
+    }
+
+    Cycle { start, length }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    // 0 -> 1 -> 2 -> 3 -> 4 -> 5 -> 6 -> 2 -> ...
+    fn step(n: &u32) -> u32 {
+        if *n == 6 {
+            2
+        } else {
+            n + 1
+        }
+    }
+
+    #[test]
+    fn test_pure_detectors_agree() {
+        let expected = Cycle {
+            start: 2,
+            length: 5,
+        };
+        assert_eq!(floyd(0, step), expected);
+        assert_eq!(brent(0, step), expected);
+        assert_eq!(
+            brent(4, step),
+            Cycle {
+                start: 0,
+                length: 5
+            }
+        );
+        assert_eq!(
+            floyd(7, |_| 7),
+            Cycle {
+                start: 0,
+                length: 1
+            }
+        );
+    }
+
+    #[test]
+    fn test_detector() {
+        let mut detector = CycleDetector::new();
+        let found: Vec<Option<Cycle>> = [1, 2, 3, 4, 2]
+            .into_iter()
+            .map(|s| detector.observe(s))
+            .collect();
+        assert_eq!(found[..4], [None, None, None, None]);
+        assert_eq!(
+            found[4],
+            Some(Cycle {
+                start: 1,
+                length: 3
+            })
+        );
+    }
+
+    #[test]
+    fn test_extrapolate() {
+        // grows by 1 and 2, then by 3, 5 and 2 repeating
+        let values = [0, 1, 3, 6, 11, 13];
+        let cycle = Cycle {
+            start: 2,
+            length: 3,
+        };
+        assert_eq!(cycle.delta(&values), 10);
+        assert_eq!(cycle.extrapolate(&values, 4), 11);
+        assert_eq!(cycle.extrapolate(&values, 6), 16);
+        assert_eq!(cycle.extrapolate(&values, 8), 23);
+        assert_eq!(cycle.extrapolate(&values, 2 + 3 * 1000), 3 + 10 * 1000);
+    }
+}
@@ -1,32 +1,237 @@
-use std::{error::Error, iter::from_fn};
-
-fn sum_batches(input: &str) -> Result<Vec<i64>, Box<dyn Error>> {
-    let mut lines = input.lines().map(str::trim);
-    let mut batch_totals: Vec<i64> = from_fn(|| {
-        let mut batch = lines.by_ref().take_while(|l| !l.is_empty()).peekable();
-        batch
-            .peek()
-            .is_some()
-            .then(|| batch.map(|l| l.parse::<i64>()).sum())
+use std::{
+    cmp::{Ordering, Reverse},
+    collections::BinaryHeap,
+    error::Error,
+    fmt::Write,
+    io::BufRead,
+    iter::from_fn,
+    ops::RangeInclusive,
+};
+
+use crate::sections::sections;
+
+type Result<T> = std::result::Result<T, Box<dyn Error>>;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Elf {
+    /// 1-based position of the elf's batch in the input.
+    pub number: usize,
+    pub items: Vec<i64>,
+    pub total: i64,
+}
+
+/// Figures for the whole expedition.
+#[derive(Debug, PartialEq)]
+pub struct Summary {
+    pub elves: usize,
+    pub mean: f64,
+    pub median: f64,
+    /// Equal-width ranges of totals, lowest first, with how many elves' totals
+    /// fall in each.
+    pub histogram: Vec<(RangeInclusive<i64>, usize)>,
+}
+
+// Orders elves by total, breaking ties in favour of the earlier elf.
+struct Ranked(Elf);
+
+impl Elf {
+    fn new(number: usize, items: Vec<i64>) -> Self {
+        Self {
+            number,
+            total: items.iter().sum(),
+            items,
+        }
+    }
+}
+
+impl Ranked {
+    fn key(&self) -> (i64, Reverse<usize>) {
+        (self.0.total, Reverse(self.0.number))
+    }
+}
+
+impl PartialEq for Ranked {
+    fn eq(&self, other: &Self) -> bool {
+        self.key() == other.key()
+    }
+}
+
+impl Eq for Ranked {}
+
+impl PartialOrd for Ranked {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Ranked {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.key().cmp(&other.key())
+    }
+}
+
+fn parse_item(line: &str, n: usize) -> Result<i64> {
+    line.trim()
+        .parse()
+        .map_err(|e| format!("line {n}: {e}").into())
+}
+
+fn elves(input: &str) -> impl Iterator<Item = Result<Elf>> + '_ {
+    sections(input).zip(1..).map(|(batch, number)| {
+        let items = batch
+            .lines()
+            .map(|(n, l)| parse_item(l, n))
+            .collect::<Result<_>>()?;
+        Ok(Elf::new(number, items))
     })
-    .collect::<Result<_, _>>()?;
+}
+
+// The same elves, read a line at a time.
+fn stream_elves(input: &mut dyn BufRead) -> impl Iterator<Item = Result<Elf>> + '_ {
+    let mut lines = input.lines().zip(1..);
+    let mut number = 0;
+    from_fn(move || {
+        let mut items = Vec::new();
+        for (line, n) in lines.by_ref() {
+            let line = match line {
+                Ok(line) => line,
+                Err(e) => return Some(Err(e.into())),
+            };
+            match line.trim() {
+                "" if !items.is_empty() => break,
+                "" => {}
+                l => match parse_item(l, n) {
+                    Ok(calories) => items.push(calories),
+                    Err(e) => return Some(Err(e)),
+                },
+            }
+        }
 
-    batch_totals.sort();
-    Ok(batch_totals)
+        (!items.is_empty()).then(|| {
+            number += 1;
+            Ok(Elf::new(number, items))
+        })
+    })
 }
 
-pub fn part1(input: &str) -> Result<String, Box<dyn Error>> {
-    sum_batches(input).map(|bs| format!("{}", bs.last().unwrap()))
+/// The `k` elves carrying the most calories, most first. Only `k` elves are
+/// held at a time, so any number of elves can be ranked.
+pub fn top_k(elves: impl Iterator<Item = Result<Elf>>, k: usize) -> Result<Vec<Elf>> {
+    let mut heap = BinaryHeap::with_capacity(k + 1);
+    for elf in elves {
+        heap.push(Reverse(Ranked(elf?)));
+        if heap.len() > k {
+            heap.pop();
+        }
+    }
+
+    Ok(heap
+        .into_sorted_vec()
+        .into_iter()
+        .map(|Reverse(Ranked(elf))| elf)
+        .collect())
 }
 
-pub fn part2(input: &str) -> Result<String, Box<dyn Error>> {
-    sum_batches(input).map(|bs| format!("{}", bs.iter().rev().take(3).sum::<i64>()))
+/// Summarises the elves' totals, splitting them into at most `buckets`
+/// equal-width ranges for the histogram.
+pub fn summarize(totals: &[i64], buckets: usize) -> Option<Summary> {
+    let min = *totals.iter().min()?;
+    let max = *totals.iter().max()?;
+
+    let mut sorted = totals.to_vec();
+    sorted.sort();
+    let mid = sorted.len() / 2;
+    let median = if sorted.len().is_multiple_of(2) {
+        (sorted[mid - 1] + sorted[mid]) as f64 / 2.0
+    } else {
+        sorted[mid] as f64
+    };
+
+    let width = ((max - min) as u64 + 1).div_ceil(buckets.max(1) as u64) as i64;
+    let mut histogram: Vec<(RangeInclusive<i64>, usize)> = (min..=max)
+        .step_by(width as usize)
+        .map(|lo| (lo..=(lo + width - 1).min(max), 0))
+        .collect();
+    for total in totals {
+        histogram[((total - min) / width) as usize].1 += 1;
+    }
+
+    Some(Summary {
+        elves: totals.len(),
+        mean: totals.iter().sum::<i64>() as f64 / totals.len() as f64,
+        median,
+        histogram,
+    })
+}
+
+/// A ranked table of the `k` best-stocked elves, followed by figures for the
+/// whole expedition.
+pub fn report(input: &str, k: usize) -> Result<String> {
+    let top = top_k(elves(input), k)?;
+    let totals: Vec<i64> = elves(input)
+        .map(|elf| Ok(elf?.total))
+        .collect::<Result<_>>()?;
+    let summary = summarize(&totals, 10).ok_or("no elves in input")?;
+
+    let mut out = String::new();
+    writeln!(out, "rank    elf  items  calories")?;
+    for (rank, elf) in top.iter().enumerate() {
+        writeln!(
+            out,
+            "{:>4} {:>6} {:>6} {:>9}",
+            rank + 1,
+            elf.number,
+            elf.items.len(),
+            elf.total
+        )?;
+    }
+
+    writeln!(
+        out,
+        "\n{} elves, mean {:.1}, median {:.1}",
+        summary.elves, summary.mean, summary.median
+    )?;
+    let tallest = summary.histogram.iter().map(|(_, n)| *n).max().unwrap_or(0);
+    for (range, n) in &summary.histogram {
+        let bar = "#".repeat((n * 40).div_ceil(tallest.max(1)));
+        writeln!(
+            out,
+            "{:>9} - {:<9} {:<40} {n}",
+            range.start(),
+            range.end(),
+            bar
+        )?;
+    }
+    Ok(out)
+}
+
+fn top_total(elves: impl Iterator<Item = Result<Elf>>, k: usize) -> Result<String> {
+    let top = top_k(elves, k)?;
+    if top.is_empty() {
+        return Err("no elves in input".into());
+    }
+    Ok(top.iter().map(|elf| elf.total).sum::<i64>().to_string())
+}
+
+pub fn part1(input: &str) -> Result<String> {
+    top_total(elves(input), 1)
+}
+
+pub fn part2(input: &str) -> Result<String> {
+    top_total(elves(input), 3)
+}
+
+pub fn part1_stream(input: &mut dyn BufRead) -> Result<String> {
+    top_total(stream_elves(input), 1)
+}
+
+pub fn part2_stream(input: &mut dyn BufRead) -> Result<String> {
+    top_total(stream_elves(input), 3)
 }
 
 #[cfg(test)]
 mod test {
-    use super::part1;
-    use super::part2;
+    use super::*;
 
     const TEST_INPUT: &str = "1000
     2000
@@ -52,4 +257,53 @@ mod test {
     fn test_part2() {
         assert_eq!(part2(TEST_INPUT).unwrap(), "45000")
     }
+
+    #[test]
+    fn test_stream() {
+        assert_eq!(part1_stream(&mut TEST_INPUT.as_bytes()).unwrap(), "24000");
+        assert_eq!(part2_stream(&mut TEST_INPUT.as_bytes()).unwrap(), "45000");
+        assert_eq!(
+            part1_stream(&mut "1\n\n2\nx\n".as_bytes())
+                .unwrap_err()
+                .to_string(),
+            "line 4: invalid digit found in string"
+        );
+    }
+
+    #[test]
+    fn test_top_k() {
+        let top = top_k(elves(TEST_INPUT), 2).unwrap();
+        assert_eq!(
+            top,
+            vec![
+                Elf::new(4, vec![7000, 8000, 9000]),
+                Elf::new(3, vec![5000, 6000]),
+            ]
+        );
+        assert_eq!(top_k(elves(TEST_INPUT), 10).unwrap().len(), 5);
+
+        // ties keep input order
+        let top = top_k(elves("5\n\n3\n2\n\n1"), 2).unwrap();
+        assert_eq!(top[0].number, 1);
+        assert_eq!(top[1].number, 2);
+    }
+
+    #[test]
+    fn test_summarize() {
+        let summary = summarize(&[6000, 4000, 11000, 24000, 10000], 4).unwrap();
+        assert_eq!(summary.elves, 5);
+        assert_eq!(summary.mean, 11000.0);
+        assert_eq!(summary.median, 10000.0);
+        assert_eq!(
+            summary.histogram,
+            vec![
+                (4000..=9000, 2),
+                (9001..=14001, 2),
+                (14002..=19002, 0),
+                (19003..=24000, 1)
+            ]
+        );
+        assert_eq!(summarize(&[1, 2], 1).unwrap().median, 1.5);
+        assert_eq!(summarize(&[], 4), None);
+    }
 }
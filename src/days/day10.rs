@@ -1,4 +1,4 @@
-use std::{error::Error, iter::repeat, mem::replace, str::FromStr};
+use std::{error::Error, io::BufRead, iter::repeat, mem::replace, str::FromStr};
 
 enum Instruction {
     Noop,
@@ -41,11 +41,11 @@ struct Processor<'a> {
 }
 
 impl<'a> Processor<'a> {
-    fn new(instructions: &'a Vec<Instruction>) -> Self {
+    fn new(instructions: impl Iterator<Item = Instruction> + 'a) -> Self {
         Self {
             x: 1,
             counter: 0,
-            instructions: Box::new(instructions.iter().flat_map(|i| i.stages())),
+            instructions: Box::new(instructions.flat_map(|i| i.stages())),
         }
     }
 }
@@ -64,32 +64,41 @@ impl<'a> Iterator for Processor<'a> {
     }
 }
 
-pub fn part1(input: &str) -> Result<String, Box<dyn Error>> {
-    let instructions: Vec<Instruction> = input
-        .lines()
-        .map(<Instruction as FromStr>::from_str)
-        .collect::<Result<_, _>>()?;
-
-    let proc = Processor::new(&instructions);
-    Ok(proc
-        .skip(19)
+fn parse_input(input: &str) -> impl Iterator<Item = Result<Instruction, Box<dyn Error>>> + '_ {
+    input.lines().map(Instruction::from_str)
+}
+
+fn parse_stream(
+    input: &mut dyn BufRead,
+) -> impl Iterator<Item = Result<Instruction, Box<dyn Error>>> + '_ {
+    input.lines().map(|l| l?.parse())
+}
+
+// Feeds the instructions to a processor as they are parsed, stopping at the
+// first one that fails.
+fn run<T>(
+    instructions: impl Iterator<Item = Result<Instruction, Box<dyn Error>>>,
+    f: impl FnOnce(Processor) -> T,
+) -> Result<T, Box<dyn Error>> {
+    let mut error = None;
+    let result = f(Processor::new(
+        instructions.map_while(|i| i.map_err(|e| error = Some(e)).ok()),
+    ));
+    error.map_or(Ok(result), Err)
+}
+
+fn signal_strength(proc: Processor) -> String {
+    proc.skip(19)
         .step_by(40)
         .map(|(counter, x)| counter * x)
         .sum::<i32>()
-        .to_string())
+        .to_string()
 }
 
-pub fn part2(input: &str) -> Result<String, Box<dyn Error>> {
-    let instructions: Vec<Instruction> = input
-        .lines()
-        .map(<Instruction as FromStr>::from_str)
-        .collect::<Result<_, _>>()?;
-
-    let proc = Processor::new(&instructions);
+fn draw(proc: Processor) -> String {
     let beam = repeat(0..40).flatten();
 
-    let output = proc
-        .zip(beam)
+    proc.zip(beam)
         .fold(String::new(), |mut acc, ((_, x), beamx)| {
             if (x - 1..=x + 1).contains(&beamx) {
                 acc.push('#');
@@ -101,9 +110,23 @@ pub fn part2(input: &str) -> Result<String, Box<dyn Error>> {
                 acc.push('\n')
             }
             acc
-        });
+        })
+}
 
-    Ok(output)
+pub fn part1(input: &str) -> Result<String, Box<dyn Error>> {
+    run(parse_input(input), signal_strength)
+}
+
+pub fn part2(input: &str) -> Result<String, Box<dyn Error>> {
+    run(parse_input(input), draw)
+}
+
+pub fn part1_stream(input: &mut dyn BufRead) -> Result<String, Box<dyn Error>> {
+    run(parse_stream(input), signal_strength)
+}
+
+pub fn part2_stream(input: &mut dyn BufRead) -> Result<String, Box<dyn Error>> {
+    run(parse_stream(input), draw)
 }
 
 #[cfg(test)]
@@ -122,4 +145,14 @@ mod test {
     fn test_part2() {
         assert_eq!(part2(INPUT).unwrap().trim_end(), OUTPUT2);
     }
+
+    #[test]
+    fn test_stream() {
+        assert_eq!(part1_stream(&mut INPUT.as_bytes()).unwrap(), "13140");
+        assert_eq!(
+            part2_stream(&mut INPUT.as_bytes()).unwrap().trim_end(),
+            OUTPUT2
+        );
+        assert!(part1_stream(&mut "noop\naddx\nnoop".as_bytes()).is_err());
+    }
 }
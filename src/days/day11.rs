@@ -1,4 +1,9 @@
-use std::{cell::RefCell, error::Error, num::ParseIntError, rc::Rc, str::FromStr};
+use std::{cell::RefCell, error::Error, rc::Rc};
+
+use crate::parse::{
+    blocks, field, literal, map, newline, or, parse_all, separated, signed, unsigned, Input,
+    Parsed, Parser,
+};
 
 #[derive(Debug)]
 enum Operation {
@@ -23,34 +28,26 @@ struct Monkey {
     total_inspections: i64,
 }
 
-impl FromStr for Operand {
-    type Err = Box<dyn Error>;
-
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
-        Ok(if s == "old" {
-            Operand::Old
-        } else {
-            Operand::Constant(s.parse()?)
-        })
+impl Operand {
+    fn parse(input: &mut Input) -> Parsed<Self> {
+        or(
+            map(literal("old"), |_| Operand::Old),
+            map(signed(), Operand::Constant),
+        )
+        .parse(input)
     }
 }
 
-impl FromStr for Operation {
-    type Err = Box<dyn Error>;
-
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let parts: Vec<&str> = s.split(' ').collect();
-        if parts.len() != 3 {
-            return Err(format!("invalid Operation: {s}").into());
-        }
-
-        let op1 = parts[0].parse()?;
-        let op2 = parts[2].parse()?;
-        match parts[1] {
-            "+" => Ok(Operation::Add(op1, op2)),
-            "*" => Ok(Operation::Mul(op1, op2)),
-            _ => Err(format!("invalid operator {} in {}", parts[1], s).into()),
-        }
+impl Operation {
+    fn parse(input: &mut Input) -> Parsed<Self> {
+        let op1 = Operand::parse(input)?;
+        let operator =
+            or(map(literal(" + "), |_| '+'), map(literal(" * "), |_| '*')).parse(input)?;
+        let op2 = Operand::parse(input)?;
+        Ok(match operator {
+            '+' => Operation::Add(op1, op2),
+            _ => Operation::Mul(op1, op2),
+        })
     }
 }
 
@@ -100,60 +97,28 @@ impl Monkey {
             .collect()
     }
 
-    fn parse_id(s: &str) -> Result<i64, Box<dyn Error>> {
-        if s.starts_with("Monkey ") && s.ends_with(':') {
-            s["Monkey ".len()..s.len() - 1]
-                .parse::<i64>()
-                .map_err(|e| e.into())
-        } else {
-            Err(format!("Not a Monkey: {s}").into())
-        }
-    }
-
-    fn parse_items(s: &str) -> Result<Vec<i64>, Box<dyn Error>> {
-        Self::parse_line(s, "Starting items:", |s| {
-            s.split(", ")
-                .map(|i| i.parse())
-                .collect::<Result<_, _>>()
-                .map_err(|e: ParseIntError| e.into())
-        })
-    }
-
-    fn parse_line<T, F>(line: &str, prefix: &str, f: F) -> Result<T, Box<dyn Error>>
-    where
-        F: FnOnce(&str) -> Result<T, Box<dyn Error>>,
-    {
-        if line.starts_with(prefix) {
-            f(&line[prefix.len()..line.len()].trim())
-        } else {
-            Err(format!("expected line starting with {prefix}, got {line}").into())
-        }
-    }
-}
-
-impl FromStr for Monkey {
-    type Err = Box<dyn Error>;
-
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let lines: Vec<&str> = s.split("\n").map(str::trim).collect();
-        if lines.len() != 6 {
-            return Err(format!("Invalid monkey - need 6 lines, got {}", lines.len()).into());
-        }
+    fn parse(input: &mut Input) -> Parsed<Self> {
+        let id = field("Monkey ", unsigned()).parse(input)?;
+        literal(":").parse(input)?;
+        newline().parse(input)?;
+        let items = field("Starting items: ", separated(signed(), literal(", "))).parse(input)?;
+        newline().parse(input)?;
+        let op = field("Operation: new = ", Operation::parse).parse(input)?;
+        newline().parse(input)?;
+        let test_divisor = field("Test: divisible by ", signed()).parse(input)?;
+        newline().parse(input)?;
+        let true_target = field("If true: throw to monkey ", signed()).parse(input)?;
+        newline().parse(input)?;
+        let false_target = field("If false: throw to monkey ", signed()).parse(input)?;
 
         Ok(Self {
             total_inspections: 0,
-            id: Self::parse_id(lines[0])?,
-            items: Self::parse_items(lines[1])?,
-            op: Self::parse_line(lines[2], "Operation: new =", Operation::from_str)?,
-            test_divisor: Self::parse_line(lines[3], "Test: divisible by", |s| {
-                s.parse().map_err(|e: ParseIntError| e.into())
-            })?,
-            true_target: Self::parse_line(lines[4], "If true: throw to monkey", |s| {
-                s.parse().map_err(|e: ParseIntError| e.into())
-            })?,
-            false_target: Self::parse_line(lines[5], "If false: throw to monkey", |s| {
-                s.parse().map_err(|e: ParseIntError| e.into())
-            })?,
+            id,
+            items,
+            op,
+            test_divisor,
+            true_target,
+            false_target,
         })
     }
 }
@@ -167,13 +132,10 @@ pub fn part2(input: &str) -> Result<String, Box<dyn Error>> {
 }
 
 fn run(input: &str, worry_divisor: i64, rounds: usize) -> Result<String, Box<dyn Error>> {
-    let monkeys: Vec<Rc<RefCell<Monkey>>> = input
-        .trim()
-        .split("\n\n")
-        .map(|m| -> Result<Rc<RefCell<Monkey>>, Box<dyn Error>> {
-            Ok(Rc::new(RefCell::new(Monkey::from_str(m)?)))
-        })
-        .collect::<Result<Vec<Rc<RefCell<_>>>, _>>()?;
+    let monkeys: Vec<Rc<RefCell<Monkey>>> = parse_all(input, blocks(Monkey::parse))?
+        .into_iter()
+        .map(|m| Rc::new(RefCell::new(m)))
+        .collect();
 
     let cleanup_mod: i64 = monkeys.iter().map(|m| m.borrow().test_divisor).product();
 
@@ -223,4 +185,13 @@ mod test {
     fn test_part2() {
         assert_eq!(part2(INPUT).unwrap(), "2713310158")
     }
+
+    #[test]
+    fn test_error_line() {
+        let input = INPUT.replace("old + 6", "old ^ 6");
+        assert!(part1(&input)
+            .unwrap_err()
+            .to_string()
+            .starts_with("line 10, column 23:"));
+    }
 }
@@ -208,4 +208,27 @@ mod test {
     fn test_part2() {
         assert_eq!(part2(INPUT).unwrap(), "29")
     }
+
+    #[test]
+    fn test_height_map_pgm() {
+        // the start is at the lowest height and the end at the highest
+        let height = |h: &u32| match char::from_u32(*h).unwrap() {
+            'S' => 0,
+            'E' => 25,
+            c => c as u16 - 'a' as u16,
+        };
+        let grid: Grid<u32> = INPUT.parse().unwrap();
+        let map = crate::grid::Grid::from_vec(grid.rows.clone());
+
+        let mut image = Vec::new();
+        map.write_pgm(&mut image, height).unwrap();
+        assert!(image.starts_with(b"P5\n8 5\n25\n"));
+        let back = crate::grid::Grid::read_pgm(&image).unwrap();
+        let heights: Vec<Vec<u16>> = grid
+            .rows
+            .iter()
+            .map(|r| r.iter().map(height).collect())
+            .collect();
+        assert_eq!(back.rows(), &heights);
+    }
 }
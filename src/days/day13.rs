@@ -4,6 +4,8 @@ use std::{
     str::{Chars, FromStr},
 };
 
+use crate::sections::{sections, Section};
+
 #[derive(Debug)]
 struct Input(Vec<[Packet; 2]>);
 
@@ -61,23 +63,21 @@ enum Token {
 }
 
 fn parse_input(s: &str) -> Result<Input, Box<dyn Error>> {
-    let mut pairs: Vec<[Packet; 2]> = vec![];
-    let mut tokens = Tokenizer {
-        input: s.chars().peekable(),
-    }
-    .peekable();
-    while let Some(pair) = parse_pair(&mut tokens)? {
-        pairs.push(pair);
-    }
-    Ok(Input(pairs))
+    sections(s)
+        .map(parse_pair)
+        .collect::<Result<_, _>>()
+        .map(Input)
 }
 
-fn parse_pair(tokens: &mut Peekable<Tokenizer>) -> Result<Option<[Packet; 2]>, Box<dyn Error>> {
-    if tokens.peek().is_none() {
-        Ok(None)
-    } else {
-        Ok(Some([parse_packet(tokens)?, parse_packet(tokens)?]))
-    }
+fn parse_pair(section: Section) -> Result<[Packet; 2], Box<dyn Error>> {
+    let packets = section
+        .lines()
+        .map(|(n, l)| l.parse().map_err(|e| format!("line {n}: {e}")))
+        .collect::<Result<Vec<Packet>, _>>()?;
+
+    packets
+        .try_into()
+        .map_err(|_| format!("line {}: expected a pair of packets", section.line).into())
 }
 
 fn parse_packet(tokens: &mut Peekable<Tokenizer>) -> Result<Packet, Box<dyn Error>> {
@@ -1,6 +1,9 @@
 use std::{error::Error, str::FromStr};
 
-use crate::grid::{Grid, Location};
+use crate::{
+    grid::{Direction8, Grid, Location},
+    record::{self, Frame},
+};
 
 #[derive(Default, PartialEq, Eq, Clone, Copy, Debug)]
 enum Material {
@@ -52,16 +55,19 @@ fn run_sand(grid: &mut Grid<Material>, mut sand: Location) -> Outcome {
         // first, figure out which of the move options (down, down+left, down+right)
         // are valid, and pass along the material type encountered at the first
         // valid move.
-        let mv = [sand.up(), sand.up().left(), sand.up().right()]
-            .iter()
-            .cloned()
-            .find_map(|l| {
-                let m = grid.get(l);
-                match m {
-                    None | Some(Material::Empty) => Some((l, m)),
-                    _ => None,
-                }
-            });
+        let mv = [
+            Direction8::Down,
+            Direction8::DownLeft,
+            Direction8::DownRight,
+        ]
+        .iter()
+        .find_map(|d| match grid.offset(sand, d.vector()) {
+            None => Some((sand, None)),
+            Some(l) => match grid.get(l) {
+                Some(Material::Empty) => Some((l, Some(&Material::Empty))),
+                _ => None,
+            },
+        });
 
         match mv {
             // If the material type is None, we fell off the grid
@@ -84,13 +90,41 @@ fn run_sand_until(mut grid: Grid<Material>, halt_on: Outcome) -> i32 {
     let mut count = 0;
     loop {
         match run_sand(&mut grid, Location::new(0, 500)) {
-            Outcome::AtRest => count += 1,
+            Outcome::AtRest => {
+                count += 1;
+                record::frame(|| Frame::from_text(&render(&grid), Some(format!("{count} grains"))));
+            }
             o if o == halt_on => return count,
             s => panic!("impossible outcome {s:?}"),
         }
     }
 }
 
+// Draws the occupied part of the grid, cropped to the columns holding stone or sand.
+fn render(grid: &Grid<Material>) -> String {
+    let cols = grid
+        .locations()
+        .filter(|l| grid.get(*l) != Some(&Material::Empty))
+        .map(|l| l.col);
+    let (min, max) = cols.fold((usize::MAX, 0), |(min, max), c| (min.min(c), max.max(c)));
+
+    grid.rows()
+        .iter()
+        .map(|row| {
+            let mut line: String = row[min..=max]
+                .iter()
+                .map(|m| match m {
+                    Material::Empty => '.',
+                    Material::Stone => '#',
+                    Material::Sand => 'o',
+                })
+                .collect();
+            line.push('\n');
+            line
+        })
+        .collect()
+}
+
 pub fn part1(input: &str) -> Result<String, Box<dyn Error>> {
     let grid = parse_input(input)?;
     return Ok(run_sand_until(grid, Outcome::Escaped).to_string());
@@ -123,4 +157,28 @@ mod test {
     fn test_part2() {
         assert_eq!(part2(INPUT).unwrap(), "93")
     }
+
+    #[test]
+    fn test_ppm_round_trip() {
+        let mut grid = parse_input(INPUT).unwrap();
+        for _ in 0..5 {
+            assert_eq!(run_sand(&mut grid, Location::new(0, 500)), Outcome::AtRest);
+        }
+
+        let colours = [
+            (Material::Empty, [0, 0, 0]),
+            (Material::Stone, [128, 128, 128]),
+            (Material::Sand, [240, 200, 80]),
+        ];
+        let mut image = Vec::new();
+        grid.write_ppm(&mut image, |m| {
+            colours.iter().find(|(c, _)| c == m).unwrap().1
+        })
+        .unwrap();
+        let back = Grid::read_ppm(&image, |rgb| {
+            colours.iter().find(|(_, c)| *c == rgb).map(|(m, _)| *m)
+        })
+        .unwrap();
+        assert_eq!(back.rows(), grid.rows());
+    }
 }
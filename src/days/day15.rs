@@ -1,4 +1,9 @@
-use std::{collections::HashSet, error::Error, ops::RangeInclusive, str::FromStr};
+use std::{error::Error, ops::RangeInclusive};
+
+use crate::{
+    interval::IntervalSet,
+    parse::{field, lines, parse_all, signed, Input, Parsed, Parser},
+};
 
 type Result<T> = std::result::Result<T, Box<dyn Error>>;
 
@@ -14,8 +19,6 @@ struct Signal {
     radius: i32,
 }
 
-type Span = (i32, i32);
-
 impl Point {
     fn taxicab_distance(&self, other: &Point) -> i32 {
         self.dx(other) + self.dy(other)
@@ -30,34 +33,21 @@ impl Point {
     }
 }
 
-impl FromStr for Signal {
-    type Err = Box<dyn Error>;
-
-    fn from_str(mut s: &str) -> std::result::Result<Self, Self::Err> {
-        if !s.starts_with("Sensor at ") {
-            return Err(format!("bad input: {s}").into());
-        }
-        s = &s["Sensor at ".len()..];
-        let points = s
-            .split_once(": closest beacon is at ")
-            .ok_or_else(|| -> Box<dyn Error> { format!("bad input: {s}").into() })?;
-
-        Ok(Self::new(points.0.parse()?, points.1.parse()?))
+impl Signal {
+    //Sensor at x=2, y=18: closest beacon is at x=-2, y=15
+    fn parse(input: &mut Input) -> Parsed<Self> {
+        let sensor = field("Sensor at ", Point::parse).parse(input)?;
+        let signal = field(": closest beacon is at ", Point::parse).parse(input)?;
+        Ok(Self::new(sensor, signal))
     }
 }
 
-impl FromStr for Point {
-    type Err = Box<dyn Error>;
-
-    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
-        //x=-399822, y=4026621
-        match s.split(&[' ', ',', '=']).collect::<Vec<_>>().as_slice() {
-            &["x", x, "", "y", y] => Ok(Self {
-                x: x.parse()?,
-                y: y.parse()?,
-            }),
-            _ => Err(format!("invaid Point: {s}").into()),
-        }
+impl Point {
+    fn parse(input: &mut Input) -> Parsed<Self> {
+        Ok(Self {
+            x: field("x=", signed()).parse(input)?,
+            y: field(", y=", signed()).parse(input)?,
+        })
     }
 }
 
@@ -71,7 +61,7 @@ impl Signal {
         }
     }
 
-    fn rowspan(&self, y: i32) -> Option<Span> {
+    fn rowspan(&self, y: i32) -> Option<RangeInclusive<i32>> {
         let dy = (self.sensor.y - y).abs();
         if dy <= self.radius() {
             // We use up dy's worth of units getting
@@ -80,7 +70,7 @@ impl Signal {
             // left to go in either direction.
             // It is valid for halfwidth to be 0.
             let halfwidth = self.radius() - dy;
-            Some((self.sensor.x - halfwidth, self.sensor.x + halfwidth))
+            Some(self.sensor.x - halfwidth..=self.sensor.x + halfwidth)
         } else {
             None
         }
@@ -92,55 +82,28 @@ impl Signal {
     }
 }
 
-fn get_exclusions(signals: &Vec<Signal>, row: i32) -> Vec<Span> {
-    let mut spans: Vec<Span> = signals.iter().filter_map(|s| s.rowspan(row)).collect();
-    spans.sort();
-    spans.iter().cloned().fold(vec![], |mut acc, span| {
-        match acc.last_mut() {
-            Some(prev) if prev.1 >= span.0 => {
-                prev.1 = prev.1.max(span.1);
-            }
-            _ => acc.push(span),
-        }
-        acc
-    })
+fn get_exclusions(signals: &[Signal], row: i32) -> IntervalSet<i32> {
+    signals.iter().filter_map(|s| s.rowspan(row)).collect()
 }
 
-fn count_exclusions_in_row(signals: &Vec<Signal>, row: i32) -> i32 {
-    let sum: i32 = get_exclusions(signals, row)
-        .iter()
-        .map(|(i, j)| (j - i) + 1)
-        .sum();
-
-    let signals_on_row = signals
-        .iter()
-        .filter_map(|sig| (sig.signal.y == row).then_some(sig.signal.x))
-        .collect::<HashSet<_>>()
-        .len() as i32;
-
-    return sum - signals_on_row;
+fn count_exclusions_in_row(signals: &[Signal], row: i32) -> u64 {
+    let mut exclusions = get_exclusions(signals, row);
+    for sig in signals.iter().filter(|sig| sig.signal.y == row) {
+        exclusions.remove(sig.signal.x..=sig.signal.x);
+    }
+    exclusions.len()
 }
 
 fn find_distress_beacon(
-    signals: &Vec<Signal>,
+    signals: &[Signal],
     xs: RangeInclusive<i32>,
     ys: RangeInclusive<i32>,
 ) -> Option<Point> {
-    for y in ys {
-        let exclusions: Vec<Span> = get_exclusions(&signals, y)
-            .iter()
-            .cloned()
-            .filter(|x| !(&x.1 < xs.start() || &x.0 > xs.end()))
-            .collect();
-
-        match exclusions.as_slice() {
-            &[ex] if ex.0 > *xs.start() => return Some(Point { x: *xs.start(), y }),
-            &[ex] if ex.1 < *xs.end() => return Some(Point { x: *xs.end(), y }),
-            &[ex, _] => return Some(Point { x: ex.1 + 1, y }),
-            _ => {}
-        };
-    }
-    None
+    ys.into_iter().find_map(|y| {
+        let candidates = get_exclusions(signals, y).complement(xs.clone());
+        let x = *candidates.iter().next()?.start();
+        Some(Point { x, y })
+    })
 }
 
 fn tuning_frequency(point: Point) -> i64 {
@@ -148,7 +111,7 @@ fn tuning_frequency(point: Point) -> i64 {
 }
 
 fn parse_input(input: &str) -> Result<Vec<Signal>> {
-    input.lines().map(Signal::from_str).collect::<Result<_>>()
+    Ok(parse_all(input, lines(Signal::parse))?)
 }
 
 pub fn part1(input: &str) -> Result<String> {
@@ -173,7 +136,10 @@ mod test {
     #[test]
     fn test_get_exclusions() {
         let signals: Vec<Signal> = parse_input(INPUT).unwrap();
-        assert_eq!(get_exclusions(&signals, 10), vec![(-2, 24)]);
+        assert_eq!(
+            get_exclusions(&signals, 10).iter().collect::<Vec<_>>(),
+            vec![-2..=24]
+        );
     }
 
     #[test]
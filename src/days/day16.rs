@@ -1,11 +1,18 @@
 use core::hash::Hash;
 use std::{
+    cell::RefCell,
     collections::{BinaryHeap, HashMap},
     error::Error,
     fmt::Debug,
     str::FromStr,
 };
 
+use crate::memo::{self, KeyBuilder, Memo};
+use crate::parse::{
+    field, identifier, lines, literal, or, parse_all, separated, signed, try_map, Input, Parsed,
+    Parser,
+};
+
 type Result<T> = std::result::Result<T, Box<dyn Error>>;
 type ID = [char; 2];
 
@@ -21,44 +28,25 @@ struct Caves {
     valves: HashMap<ID, Valve>,
 }
 
-fn parse_id(s: &str) -> Result<ID> {
-    if s.chars().count() == 2 {
-        let mut c = s.chars();
-        Ok([c.next().unwrap(), c.next().unwrap()])
-    } else {
-        Err(format!("invalid id '{s}'").into())
-    }
+fn parse_id(input: &mut Input) -> Parsed<ID> {
+    try_map(identifier(), |s| match s.chars().collect::<Vec<_>>()[..] {
+        [a, b] => Ok([a, b]),
+        _ => Err(format!("a two letter valve id instead of '{s}'")),
+    })
+    .parse(input)
 }
 
-impl FromStr for Valve {
-    type Err = Box<dyn Error>;
-
-    fn from_str(s: &str) -> Result<Self> {
-        let mut tokens = s.split(' ');
-        tokens.next();
-
-        let id = parse_id(tokens.next().ok_or_else(|| "missing ID".to_string())?)?;
-
-        tokens.next();
-        tokens.next();
-
-        let flow_rate = tokens.next().ok_or_else(|| "missing rate".to_string())?;
-        let flow_rate: i64 = flow_rate
-            .split_once('=')
-            .ok_or_else(|| "invalid rate".to_string())?
-            .1
-            .trim_end_matches(';')
-            .parse()
-            .map_err(|e| -> Box<dyn Error> { format!("invalid rate {e}").into() })?;
-
-        tokens.next();
-        tokens.next();
-        tokens.next();
-        tokens.next();
-
-        let neighbors: Vec<ID> = tokens
-            .map(|s| parse_id(s.trim_end_matches(',')))
-            .collect::<Result<Vec<_>>>()?;
+impl Valve {
+    //Valve AA has flow rate=0; tunnels lead to valves DD, II, BB
+    fn parse(input: &mut Input) -> Parsed<Self> {
+        let id = field("Valve ", parse_id).parse(input)?;
+        let flow_rate = field(" has flow rate=", signed()).parse(input)?;
+        or(
+            literal("; tunnels lead to valves "),
+            literal("; tunnel leads to valve "),
+        )
+        .parse(input)?;
+        let neighbors = separated(parse_id, literal(", ")).parse(input)?;
 
         Ok(Self {
             id,
@@ -68,17 +56,22 @@ impl FromStr for Valve {
     }
 }
 
+impl FromStr for Valve {
+    type Err = Box<dyn Error>;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Ok(parse_all(s, Valve::parse)?)
+    }
+}
+
 impl FromStr for Caves {
     type Err = Box<dyn Error>;
 
     fn from_str(s: &str) -> Result<Self> {
-        let valves = s
-            .lines()
-            .map(|l| {
-                let v: Valve = l.parse()?;
-                Ok((v.id, v))
-            })
-            .collect::<Result<_>>()?;
+        let valves = parse_all(s, lines(Valve::parse))?
+            .into_iter()
+            .map(|v| (v.id, v))
+            .collect();
 
         Ok(Self { valves })
     }
@@ -158,6 +151,9 @@ struct Searcher {
     caves: Caves,
     edges: HashMap<ID, Vec<Edge<ID>>>,
     nodenames: Vec<ID>,
+    // each node's bit in a visited set
+    bits: HashMap<ID, u64>,
+    memo: RefCell<Memo<u128, i64>>,
 }
 
 impl Searcher {
@@ -211,47 +207,60 @@ impl Searcher {
             summarized.insert(src.clone(), edges);
         }
 
-        let nodenames = summarized.keys().cloned().collect();
+        let nodenames: Vec<ID> = summarized.keys().cloned().collect();
+        let bits = nodenames
+            .iter()
+            .enumerate()
+            .map(|(i, id)| (*id, 1 << i))
+            .collect();
 
         Self {
             caves,
             edges: summarized,
             nodenames,
+            bits,
+            memo: RefCell::new(Memo::new()),
         }
     }
 
-    fn find_best(
-        &self,
-        start: ID,
-        visited: &mut Vec<ID>,
-        mut time_remaining: i64,
-        mut released: i64,
-    ) -> i64 {
+    fn visited_set<'a>(&self, ids: impl IntoIterator<Item = &'a ID>) -> u64 {
+        ids.into_iter().fold(0, |acc, id| acc | self.bits[id])
+    }
+
+    // The most pressure that can be released by opening `start` and then
+    // moving on to valves outside `visited`.
+    fn find_best(&self, start: ID, visited: u64, mut time_remaining: i64) -> i64 {
         // opening valve takes 1 minute
         time_remaining = time_remaining - 1;
         if time_remaining < 0 {
-            return released;
+            return 0;
+        }
+        let visited = visited | self.bits[&start];
+
+        let key = KeyBuilder::new()
+            .push(self.bits[&start].trailing_zeros() as u64, 6)
+            .push(visited, 64)
+            .push(time_remaining as u64, 8)
+            .build();
+        if let Some(best) = self.memo.borrow_mut().get(&key) {
+            return best;
         }
-        visited.push(start.clone());
-
-        released += time_remaining * self.caves.valves[&start].flow_rate;
 
+        let released = time_remaining * self.caves.valves[&start].flow_rate;
         let best = self.edges[&start]
             .iter()
             .filter_map(|e| {
-                if time_remaining <= e.cost || visited.contains(&e.node) {
+                if time_remaining <= e.cost || visited & self.bits[&e.node] != 0 {
                     None
                 } else {
-                    Some(self.find_best(e.node, visited, time_remaining - e.cost, released))
+                    Some(self.find_best(e.node, visited, time_remaining - e.cost))
                 }
             })
             .max()
-            .unwrap_or(released);
+            .unwrap_or(0);
 
-        let popped = visited.pop();
-        debug_assert_eq!(popped, Some(start.clone()));
-
-        best
+        self.memo.borrow_mut().insert(key, released + best);
+        released + best
     }
 }
 
@@ -275,7 +284,7 @@ pub fn part1(input: &str) -> Result<String> {
     let start: ID = ['A', 'A'];
     let searcher = Searcher::new(start, caves);
 
-    Ok(searcher.find_best(start, &mut vec![], 31, 0).to_string())
+    Ok(searcher.find_best(start, 0, 31).to_string())
 }
 
 #[allow(unused_variables)]
@@ -292,14 +301,10 @@ pub fn part2(input: &str) -> Result<String> {
     let best = all_subsets
         .iter()
         .map(|ss| {
-            let mut elephant_subset: Vec<ID> = searcher
-                .nodenames
-                .iter()
-                .filter(|n| !ss.contains(n))
-                .cloned()
-                .collect();
-            let elf_best = searcher.find_best(start, &mut elephant_subset, 27, 0);
-            let elph_best = searcher.find_best(start, &mut ss.clone(), 27, 0);
+            let elephant_subset =
+                searcher.visited_set(searcher.nodenames.iter().filter(|n| !ss.contains(n)));
+            let elf_best = searcher.find_best(start, elephant_subset, 27);
+            let elph_best = searcher.find_best(start, searcher.visited_set(ss), 27);
             elf_best + elph_best
         })
         .max()
@@ -310,6 +315,7 @@ pub fn part2(input: &str) -> Result<String> {
         all_subsets.len(),
         searcher.nodenames.len()
     );
+    memo::report("day 16 valves", searcher.memo.borrow().stats());
 
     Ok(best.to_string())
 }
@@ -339,6 +345,14 @@ mod test {
                 neighbors: vec![['G', 'G']],
             }
         );
+
+        assert_eq!(
+            "Valve AA has flow rate=0; tunnels lead to valves DD, II\nValve BBB has flow rate=13"
+                .parse::<Caves>()
+                .unwrap_err()
+                .to_string(),
+            "line 2, column 7: expected a two letter valve id instead of 'BBB', found 'BBB has flow rate=13'"
+        );
     }
 
     #[test]
@@ -1,7 +1,10 @@
-use std::{
-    error::Error,
-    fmt::{Display, Write},
-    time::Instant,
+use std::{error::Error, fmt::Display};
+
+use crate::{
+    bitgrid::BitGrid,
+    cycle::CycleDetector,
+    grid::Location,
+    record::{self, Frame},
 };
 
 type Result<T> = std::result::Result<T, Box<dyn Error>>;
@@ -12,11 +15,6 @@ enum Move {
     Right,
 }
 
-fn rev<T>(mut v: Vec<T>) -> Vec<T> {
-    v.reverse();
-    v
-}
-
 #[derive(Debug, Clone, Copy, PartialEq)]
 enum PieceType {
     HBar,
@@ -28,50 +26,18 @@ enum PieceType {
 
 impl Into<Piece> for PieceType {
     fn into(self) -> Piece {
-        match self {
-            PieceType::HBar => Piece {
-                col: 2,
-                width: 4,
-                height: 1,
-                blocks: rev(vec![false, false, true, true, true, true, false]),
-            },
-            PieceType::Plus => Piece {
-                col: 2,
-                width: 3,
-                height: 3,
-                blocks: rev(vec![
-                    false, false, false, true, false, false, false, false, false, true, true, true,
-                    false, false, false, false, false, true, false, false, false,
-                ]),
-            },
-            PieceType::BigL => Piece {
-                col: 2,
-                width: 3,
-                height: 3,
-                blocks: rev(vec![
-                    false, false, false, false, true, false, false, false, false, false, false,
-                    true, false, false, false, false, true, true, true, false, false,
-                ]),
-            },
-            PieceType::VBar => Piece {
-                col: 2,
-                width: 1,
-                height: 4,
-                blocks: rev(vec![
-                    false, false, true, false, false, false, false, false, false, true, false,
-                    false, false, false, false, false, true, false, false, false, false, false,
-                    false, true, false, false, false, false,
-                ]),
-            },
-            PieceType::Block => Piece {
-                col: 2,
-                width: 2,
-                height: 2,
-                blocks: rev(vec![
-                    false, false, true, true, false, false, false, false, false, true, true, false,
-                    false, false,
-                ]),
-            },
+        // Board rows count upwards from the floor, so shapes are listed bottom row first.
+        let rows: &[&str] = match self {
+            PieceType::HBar => &["####"],
+            PieceType::Plus => &[".#.", "###", ".#."],
+            PieceType::BigL => &["###", "..#", "..#"],
+            PieceType::VBar => &["#", "#", "#", "#"],
+            PieceType::Block => &["##", "##"],
+        };
+
+        Piece {
+            col: 2,
+            shape: BitGrid::from_rows(rows.iter().copied()),
         }
     }
 }
@@ -79,9 +45,7 @@ impl Into<Piece> for PieceType {
 #[derive(Clone)]
 struct Piece {
     col: usize,
-    width: usize,
-    height: usize,
-    blocks: Vec<bool>,
+    shape: BitGrid,
 }
 
 fn pieces() -> Vec<PieceType> {
@@ -112,12 +76,10 @@ impl Piece {
         match mv {
             Move::Left if self.col > 0 => {
                 self.col = self.col - 1;
-                self.blocks.rotate_right(1);
                 true
             }
-            Move::Right if self.col + self.width < BOARD_WIDTH => {
+            Move::Right if self.col + self.shape.width() < BOARD_WIDTH => {
                 self.col = self.col + 1;
-                self.blocks.rotate_left(1);
                 true
             }
             _ => false,
@@ -128,8 +90,9 @@ impl Piece {
 struct Game<'a> {
     pieces: Box<dyn Iterator<Item = PieceType> + 'a>,
     moves: Box<dyn Iterator<Item = Move> + 'a>,
-    board: Vec<bool>,
+    board: BitGrid,
     piece_count: i64,
+    move_count: usize,
 }
 
 impl<'a> Game<'a> {
@@ -137,15 +100,16 @@ impl<'a> Game<'a> {
         Self {
             pieces: Box::new(piece_spec.iter().cloned().cycle()),
             moves: Box::new(move_spec.iter().cloned().cycle()),
-            board: vec![],
+            board: BitGrid::new(BOARD_WIDTH, 0),
             piece_count: 0,
+            move_count: 0,
         }
     }
     fn run_piece(&mut self) -> (PieceType, usize) {
         self.piece_count += 1;
         let piece_type = self.pieces.next().unwrap().clone();
         let mut piece: Piece = piece_type.into();
-        self.expand_board(piece.height);
+        self.expand_board(piece.shape.height());
         let mut piece_row = self.block_height() + 3;
 
         // self.print_board(&piece, piece_row, "Spawned new piece");
@@ -154,7 +118,7 @@ impl<'a> Game<'a> {
         // because pieces cannot get stuck at this stage.
         for _ in 0..3 {
             // alternate applying gas jets...
-            let mv = self.moves.next().unwrap();
+            let mv = self.next_move();
             #[allow(unused)]
             let moved = piece.apply_move(mv);
             // if moved {
@@ -170,7 +134,7 @@ impl<'a> Game<'a> {
         // Now, we're overlapping with the board, which means pieces
         // can collide with the existing pieces.
         loop {
-            let mv = self.moves.next().unwrap();
+            let mv = self.next_move();
             let mut newpiece = piece.clone();
             newpiece.apply_move(mv.clone());
             if !self.check_collision(&newpiece, piece_row) {
@@ -185,6 +149,16 @@ impl<'a> Game<'a> {
             if piece_row == 0 || self.check_collision(&piece, piece_row - 1) {
                 self.print_board(&piece, piece_row, "Piece landed");
                 self.land(piece, piece_row);
+                record::frame(|| {
+                    Frame::from_text(
+                        &self.render_top(40),
+                        Some(format!(
+                            "pieces={} height={}",
+                            self.piece_count,
+                            self.block_height()
+                        )),
+                    )
+                });
                 break;
             } else {
                 piece_row -= 1;
@@ -195,44 +169,57 @@ impl<'a> Game<'a> {
         return (piece_type, self.block_height());
     }
 
+    fn next_move(&mut self) -> Move {
+        self.move_count += 1;
+        self.moves.next().unwrap()
+    }
+
+    // how far below the top of the stack the highest block in each column is;
+    // together with the piece and move positions, this is enough to tell when
+    // the game starts repeating itself
+    fn surface(&self) -> [usize; BOARD_WIDTH] {
+        let top = self.block_height();
+        let mut depths = [top; BOARD_WIDTH];
+        for (col, depth) in depths.iter_mut().enumerate() {
+            if let Some(row) = (0..top)
+                .rev()
+                .find(|row| self.board.get(Location::new(*row, col)))
+            {
+                *depth = top - 1 - row;
+            }
+        }
+        depths
+    }
+
     // land a piece at a particular row, copying its blocks into
     // the game grid and dropping the original piece
     fn land(&mut self, piece: Piece, row: usize) {
-        let start = row * BOARD_WIDTH;
-        for (i, &b) in piece.blocks.iter().enumerate() {
-            if b {
-                self.board[start + i] = b;
-            }
-        }
+        self.board
+            .stamp(&piece.shape, Location::new(row, piece.col));
     }
 
     #[must_use]
     fn check_collision(&self, piece: &Piece, row: usize) -> bool {
-        let start = row * BOARD_WIDTH;
-        piece
-            .blocks
-            .iter()
-            .zip(&self.board[start..])
-            .any(|(pb, bb)| *pb && *bb)
+        self.board
+            .collides(&piece.shape, Location::new(row, piece.col))
     }
 
     fn expand_board(&mut self, piece: usize) {
-        let required = (self.block_height() + 3 + piece) * BOARD_WIDTH;
-        if self.board.len() < required {
-            self.board.resize(required, false);
+        let required = self.block_height() + 3 + piece;
+        if self.board.height() < required {
+            self.board.resize_rows(required);
         }
     }
 
     fn board_height(&self) -> usize {
-        self.board.len() / 7
+        self.board.height()
     }
 
     fn block_height(&self) -> usize {
-        if let Some(last_set) = self.board.iter().rev().position(|b| *b) {
-            (self.board.len() / BOARD_WIDTH) - (last_set / BOARD_WIDTH)
-        } else {
-            0
-        }
+        (0..self.board.height())
+            .rev()
+            .find(|row| self.board.row_any(*row))
+            .map_or(0, |row| row + 1)
     }
 
     #[allow(unused)]
@@ -261,19 +248,30 @@ impl<'a> Game<'a> {
     }
 }
 
-impl<'a> Display for Game<'a> {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        for (rowinv, chunk) in self.board.chunks(BOARD_WIDTH).enumerate().rev().take(500) {
-            f.write_char('|')?;
-            for b in chunk.iter().rev() {
-                f.write_char(if *b { '#' } else { '.' })?;
+impl<'a> Game<'a> {
+    // draw the topmost `rows` rows of the board, with the floor once it's in view
+    fn render_top(&self, rows: usize) -> String {
+        let mut out = String::new();
+        for rowinv in (0..self.board.height()).rev().take(rows) {
+            out.push('|');
+            for col in 0..BOARD_WIDTH {
+                let b = self.board.get(Location::new(rowinv, col));
+                out.push(if b { '#' } else { '.' });
             }
-            f.write_char('|')?;
-            f.write_str(&format!(" {}", rowinv))?;
-            f.write_char('\n')?;
+            out.push('|');
+            out.push_str(&format!(" {}", rowinv));
+            out.push('\n');
         }
-        f.write_str("+-------+\n")?;
-        Ok(())
+        if self.board.height() <= rows {
+            out.push_str("+-------+\n");
+        }
+        out
+    }
+}
+
+impl<'a> Display for Game<'a> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.render_top(500))
     }
 }
 
@@ -297,65 +295,23 @@ pub fn part2(input: &str) -> Result<String> {
     let all_pieces = pieces();
     let mut game = Game::new(&all_pieces, &moves);
 
-    let start = Instant::now();
-    let test_tick = 100000;
-    let mut history: Vec<(PieceType, usize)> = Vec::with_capacity(10000);
-    let target_pieces: i64 = 1000000000000;
+    let target_pieces = 1000000000000;
+    let mut detector = CycleDetector::new();
+    // heights[i] is the height of the stack after i pieces
+    let mut heights = vec![0];
     loop {
-        let height = game.block_height();
-        let (piecetype, _) = game.run_piece();
-        let height_delta = game.block_height() - height;
-        history.push((piecetype, height_delta));
-        if history.len() > 20000 {
-            if let Some(cycle) = find_cycle(&history[history.len() - 10000..], 20) {
-                println!(
-                    "Found cycle at {} with len {}",
-                    history.len() - 10000,
-                    cycle.len(),
-                );
-
-                let pre_len = history.len() - 10000;
-                let cycle_delta: i64 = cycle.iter().map(|(_, dh)| *dh as i64).sum();
-                let total_height: i64 = history[0..pre_len].iter().map(|(_, dh)| *dh as i64).sum();
-
-                let repetitions = (target_pieces - pre_len as i64) / cycle.len() as i64;
-                let remainder: usize = (target_pieces as usize - pre_len) % cycle.len();
-                let remainder_delta: i64 =
-                    cycle.iter().take(remainder).map(|(_, dh)| *dh as i64).sum();
-
-                let grand_total = total_height + repetitions * cycle_delta + remainder_delta;
-                return Ok(grand_total.to_string());
-            }
-        }
-
-        if game.piece_count == test_tick {
-            let now = Instant::now();
-            let elapsed = now - start;
-            println!("{test_tick} ticks in {}ms", elapsed.as_millis());
-            println!(
-                "estimated total runtime: {}s",
-                (elapsed * ((1000000000000 / test_tick) as u32)).as_secs()
-            );
-        }
-        if game.piece_count == 1000000000000 {
-            return Ok(game.block_height().to_string());
+        let state = (
+            game.piece_count as usize % all_pieces.len(),
+            game.move_count % moves.len(),
+            game.surface(),
+        );
+        if let Some(cycle) = detector.observe(state) {
+            return Ok(cycle.extrapolate(&heights, target_pieces).to_string());
         }
-    }
-}
 
-fn find_cycle<'a, T>(ts: &'a [T], minlen: usize) -> Option<&'a [T]>
-where
-    T: PartialEq + std::fmt::Debug,
-{
-    assert!(minlen > 1, "minimum length must be > 1");
-    let maxlen = ts.len() / 2;
-    for len in minlen..=maxlen {
-        let start = 0;
-        if ts[start..start + len] == ts[start + len..start + 2 * len] {
-            return Some(&ts[start..start + len]);
-        }
+        game.run_piece();
+        heights.push(game.block_height() as i64);
     }
-    None
 }
 
 #[cfg(test)]
@@ -365,25 +321,6 @@ mod test {
     const INPUT: &str = include_str!("tests/day17test.txt");
     const LINE_BY_LINE: &str = include_str!("tests/day17testlines.txt");
 
-    #[test]
-    fn test_find_cycle() {
-        assert_eq!(find_cycle(&[1, 2, 3, 4, 5, 6, 7, 8], 2), None);
-        assert_eq!(
-            find_cycle(&[1, 2, 3, 4, 1, 2, 3, 4], 2).unwrap(),
-            &[1, 2, 3, 4][..]
-        );
-        assert_eq!(find_cycle(&[1, 2, 3, 4, 2, 3, 4], 2), None);
-        assert_eq!(
-            find_cycle(&[1, 2, 3, 4, 2, 3, 4][1..], 2).unwrap(),
-            &[2, 3, 4][..]
-        );
-
-        assert_eq!(
-            find_cycle(&[1, 2, 3, 4, 3, 4, 2, 3, 4, 3, 4][1..], 5).unwrap(),
-            &[2, 3, 4, 3, 4][..]
-        );
-    }
-
     #[test]
     fn test_part1() {
         assert_eq!(part1(INPUT).unwrap(), "3068")
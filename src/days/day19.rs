@@ -1,6 +1,7 @@
-use std::{collections::VecDeque, time::Instant};
-#[allow(unused)]
-use std::{error::Error, num::ParseIntError, str::FromStr};
+use std::{collections::VecDeque, error::Error, time::Instant};
+
+use crate::memo::{self, KeyBuilder, Memo};
+use crate::parse::{field, lines, literal, parse_all, unsigned, Input, Parsed, Parser};
 
 type Result<T> = std::result::Result<T, Box<dyn Error>>;
 
@@ -117,9 +118,27 @@ impl Blueprint {
         let mut q = VecDeque::new();
         q.push_front(start.clone());
 
+        // Different build orders often reach the same state; only the first
+        // arrival needs exploring. The limit bounds memory on long runs, at
+        // the cost of sometimes exploring a state twice.
+        let mut seen: Memo<u128, ()> = Memo::with_limit(1 << 22);
+        let max_cost = [Resource::Ore, Resource::Clay, Resource::Obsidian].map(|resource| {
+            [&self.ore, &self.clay, &self.obsidian, &self.geode]
+                .iter()
+                .flat_map(|r| r.cost.iter())
+                .filter_map(|(r, cost)| (*r == resource).then_some(*cost))
+                .max()
+                .unwrap_or(0)
+        });
         let mut best = None;
 
         while let Some(sq) = q.pop_front() {
+            let key = sq.key(&max_cost);
+            if seen.get(&key).is_some() {
+                continue;
+            }
+            seen.insert(key, ());
+
             debug_assert!(sq.time <= sq.max_time);
             best = match best {
                 None => Some(sq.clone()),
@@ -135,6 +154,7 @@ impl Blueprint {
             self.id,
             elapsed.as_millis()
         );
+        memo::report(&format!("blueprint {} states", self.id), seen.stats());
         best
     }
 
@@ -192,6 +212,30 @@ impl Default for State {
 }
 
 impl State {
+    // Everything that affects what can happen next. `final_geodes` follows
+    // from the geodes and geode robots, and `built` is only a note. Stock
+    // beyond what could be spent by building the most expensive robot every
+    // remaining minute makes no difference, so it is left out.
+    fn key(&self, max_cost: &[i32; 3]) -> u128 {
+        let mut key = KeyBuilder::new()
+            .push(self.time as u64, 6)
+            .push(self.max_time as u64, 6);
+        let stock = [self.ore, self.clay, self.obsidian];
+        for (n, cost) in stock.into_iter().zip(max_cost) {
+            key = key.push(n.min(cost * self.time_left()) as u64, 16);
+        }
+        key = key.push(self.geodes as u64, 16);
+        for n in [
+            self.ore_robots,
+            self.clay_robots,
+            self.obsidian_robots,
+            self.geode_robots,
+        ] {
+            key = key.push(n as u64, 8);
+        }
+        key.build()
+    }
+
     fn time_left(&self) -> i32 {
         self.max_time - self.time
     }
@@ -293,43 +337,40 @@ impl State {
     }
 }
 
-impl FromStr for Blueprint {
-    type Err = Box<dyn Error>;
-
-    fn from_str(s: &str) -> Result<Self> {
-        let mut parts = s.split(&[' ', ':']);
-        let mut nth = |n, field: &str| -> Result<i32> {
-            let value = parts.nth(n).ok_or_else(|| -> Box<dyn Error> {
-                format!("Invalid blueprint (could not parse {field}): {s}").into()
-            })?;
-
-            value.parse().map_err(|e: ParseIntError| {
-                format!("Could not parse {field} value '{value}': {e}").into()
-            })
-        };
+impl Blueprint {
+    fn parse(input: &mut Input) -> Parsed<Self> {
+        let id = field("Blueprint ", unsigned()).parse(input)?;
+        let ore_robot_ore = field(": Each ore robot costs ", unsigned()).parse(input)?;
+        let clay_robot_ore = field(" ore. Each clay robot costs ", unsigned()).parse(input)?;
+        let obsidian_robot_ore =
+            field(" ore. Each obsidian robot costs ", unsigned()).parse(input)?;
+        let obsidian_robot_clay = field(" ore and ", unsigned()).parse(input)?;
+        let geode_robot_ore = field(" clay. Each geode robot costs ", unsigned()).parse(input)?;
+        let geode_robot_obsidian = field(" ore and ", unsigned()).parse(input)?;
+        literal(" obsidian.").parse(input)?;
 
         Ok(Self {
-            id: nth(1, "id")?,
+            id,
             ore: RobotRecipe {
                 produces: Resource::Ore,
-                cost: vec![(Resource::Ore, nth(5, "ore_robot_ore_cost")?)],
+                cost: vec![(Resource::Ore, ore_robot_ore)],
             },
             clay: RobotRecipe {
                 produces: Resource::Clay,
-                cost: vec![(Resource::Ore, nth(5, "clay_robot_ore_cost")?)],
+                cost: vec![(Resource::Ore, clay_robot_ore)],
             },
             obsidian: RobotRecipe {
                 produces: Resource::Obsidian,
                 cost: vec![
-                    (Resource::Ore, nth(5, "obsidian_robot_ore_cost")?),
-                    (Resource::Clay, nth(2, "obsidian_robot_clay_cost")?),
+                    (Resource::Ore, obsidian_robot_ore),
+                    (Resource::Clay, obsidian_robot_clay),
                 ],
             },
             geode: RobotRecipe {
                 produces: Resource::Geode,
                 cost: vec![
-                    (Resource::Ore, nth(5, "geode_robot_ore_cost")?),
-                    (Resource::Obsidian, nth(2, "geode_robot_obsidian_cost")?),
+                    (Resource::Ore, geode_robot_ore),
+                    (Resource::Obsidian, geode_robot_obsidian),
                 ],
             },
         })
@@ -337,7 +378,7 @@ impl FromStr for Blueprint {
 }
 
 fn parse_input(input: &str) -> Result<Vec<Blueprint>> {
-    input.trim().lines().map(Blueprint::from_str).collect()
+    Ok(parse_all(input, lines(Blueprint::parse))?)
 }
 
 pub fn part1(input: &str) -> Result<String> {
@@ -1,55 +1,318 @@
-use std::{collections::HashMap, error::Error};
+use std::{error::Error, fmt::Write, str::FromStr};
 
-fn summarize_human_readable(
-    input: &str,
-    scores: &[(&str, i64)], //&HashMap<&str, i64>,
-) -> Result<String, Box<dyn Error>> {
-    let scores: HashMap<_, _> = scores.iter().cloned().collect();
+type Result<T> = std::result::Result<T, Box<dyn Error>>;
+
+const CLASSIC: &str = "rock 1 beats scissors
+paper 2 beats rock
+scissors 3 beats paper";
+
+const LIZARD_SPOCK: &str = "rock 1 beats scissors lizard
+paper 2 beats rock spock
+scissors 3 beats paper lizard
+lizard 4 beats spock paper
+spock 5 beats scissors rock";
+
+#[derive(Debug, PartialEq)]
+struct Shape {
+    name: String,
+    score: i64,
+    beats: Vec<usize>,
+}
+
+/// The shapes of a game and which beats which. Shapes are referred to by
+/// their position in the rules, which is also how the strategy guide's
+/// letters map onto them: `A` (or `X`) is the first shape, and so on.
+#[derive(Debug, PartialEq)]
+struct Rules {
+    shapes: Vec<Shape>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Outcome {
+    Lose,
+    Draw,
+    Win,
+}
+
+/// How to read the second column of the strategy guide.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Decoding {
+    /// Each letter is the shape to play, given as an index into the rules:
+    /// `X` plays `shapes[0]`, `Y` plays `shapes[1]`, and so on.
+    Shapes(Vec<usize>),
+    /// `X`, `Y` and `Z` mean lose, draw and win.
+    Outcomes,
+}
+
+#[derive(Debug, PartialEq)]
+struct Round {
+    theirs: usize,
+    mine: usize,
+    outcome: Outcome,
+    shape_points: i64,
+    outcome_points: i64,
+    /// The score of this round and all those before it.
+    running_total: i64,
+}
+
+impl Outcome {
+    const ALL: [Outcome; 3] = [Outcome::Lose, Outcome::Draw, Outcome::Win];
+
+    fn score(&self) -> i64 {
+        match self {
+            Outcome::Lose => 0,
+            Outcome::Draw => 3,
+            Outcome::Win => 6,
+        }
+    }
+}
+
+impl Rules {
+    fn outcome(&self, mine: usize, theirs: usize) -> Outcome {
+        if self.shapes[mine].beats.contains(&theirs) {
+            Outcome::Win
+        } else if self.shapes[theirs].beats.contains(&mine) {
+            Outcome::Lose
+        } else {
+            Outcome::Draw
+        }
+    }
+
+    /// The first shape that gets `outcome` against `theirs`.
+    fn shape_for(&self, theirs: usize, outcome: Outcome) -> Option<usize> {
+        (0..self.shapes.len()).find(|mine| self.outcome(*mine, theirs) == outcome)
+    }
+
+    /// Plays out `guide`, a list of (opponent letter, my letter) pairs given
+    /// as 0-based indices, and scores each round.
+    fn play(&self, guide: &[(usize, usize)], decoding: &Decoding) -> Result<Vec<Round>> {
+        let mut running_total = 0;
+        guide
+            .iter()
+            .map(|&(theirs, code)| {
+                // before anything looks their shape up
+                if theirs >= self.shapes.len() {
+                    let letter = (b'A' + theirs as u8) as char;
+                    return Err(format!("can't read '{letter}' as a shape").into());
+                }
+                let mine = match decoding {
+                    Decoding::Shapes(shapes) => shapes.get(code).copied(),
+                    Decoding::Outcomes => Outcome::ALL
+                        .get(code)
+                        .and_then(|outcome| self.shape_for(theirs, *outcome)),
+                }
+                .filter(|mine| *mine < self.shapes.len())
+                .ok_or_else(|| {
+                    let meaning = match decoding {
+                        Decoding::Shapes(_) => "a shape",
+                        Decoding::Outcomes => "an outcome",
+                    };
+                    format!("can't read '{}' as {meaning}", my_letter(code))
+                })?;
+
+                let outcome = self.outcome(mine, theirs);
+                let shape_points = self.shapes[mine].score;
+                running_total += shape_points + outcome.score();
+                Ok(Round {
+                    theirs,
+                    mine,
+                    outcome,
+                    shape_points,
+                    outcome_points: outcome.score(),
+                    running_total,
+                })
+            })
+            .collect()
+    }
+
+    /// Tries reading the guide's second column as outcomes and as every
+    /// assignment of letters to shapes, and returns the best decoding with its
+    /// score. Ties go to the outcome decoding, then to the earliest
+    /// assignment.
+    fn best_decoding(&self, guide: &[(usize, usize)]) -> Result<(Decoding, i64)> {
+        let mut best: Option<(Decoding, i64)> = None;
+        let candidates = std::iter::once(Decoding::Outcomes)
+            .chain(permutations(self.shapes.len()).map(Decoding::Shapes));
+        for decoding in candidates {
+            let Ok(rounds) = self.play(guide, &decoding) else {
+                continue;
+            };
+            let score = total(&rounds);
+            if best.as_ref().is_none_or(|(_, s)| score > *s) {
+                best = Some((decoding, score));
+            }
+        }
+        best.ok_or_else(|| "no decoding fits the strategy guide".into())
+    }
+}
+
+// Every ordering of 0..n, in lexicographic order.
+fn permutations(n: usize) -> impl Iterator<Item = Vec<usize>> {
+    let mut next = Some((0..n).collect::<Vec<_>>());
+    std::iter::from_fn(move || {
+        let current = next.take()?;
+        let mut p = current.clone();
+        if let Some(i) = (1..p.len()).rev().find(|&i| p[i - 1] < p[i]) {
+            let j = (i..p.len()).rev().find(|&j| p[j] > p[i - 1]).unwrap();
+            p.swap(i - 1, j);
+            p[i..].reverse();
+            next = Some(p);
+        }
+        Some(current)
+    })
+}
+
+fn total(rounds: &[Round]) -> i64 {
+    rounds.last().map_or(0, |r| r.running_total)
+}
+
+/// Parses rules written one shape per line, in the order the guide's letters
+/// refer to them: `<name> <score> beats <name>...`.
+impl FromStr for Rules {
+    type Err = Box<dyn Error>;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let lines: Vec<Vec<&str>> = s
+            .lines()
+            .map(|l| l.split_whitespace().collect())
+            .filter(|words: &Vec<&str>| !words.is_empty())
+            .collect();
+        let names: Vec<&str> = lines.iter().map(|words| words[0]).collect();
+        let index = |name: &str| {
+            names
+                .iter()
+                .position(|n| *n == name)
+                .ok_or_else(|| format!("unknown shape '{name}'"))
+        };
+
+        let shapes = lines
+            .iter()
+            .map(|words| match words.as_slice() {
+                [name, score, "beats", beaten @ ..] => Ok(Shape {
+                    name: name.to_string(),
+                    score: score.parse()?,
+                    beats: beaten
+                        .iter()
+                        .map(|b| index(b))
+                        .collect::<std::result::Result<_, _>>()?,
+                }),
+                _ => Err(format!("invalid shape '{}'", words.join(" ")).into()),
+            })
+            .collect::<Result<Vec<Shape>>>()?;
+
+        for (i, shape) in shapes.iter().enumerate() {
+            if names[..i].contains(&shape.name.as_str()) {
+                return Err(format!("shape '{}' defined twice", shape.name).into());
+            }
+            if let Some(b) = shape
+                .beats
+                .iter()
+                .find(|b| **b == i || shapes[**b].beats.contains(&i))
+            {
+                return Err(format!("'{}' and '{}' can't both win", shape.name, names[*b]).into());
+            }
+        }
+        Ok(Self { shapes })
+    }
+}
+
+const MY_LETTERS: &str = "XYZABCDEFGHIJKLMNOPQRSTUVW";
+
+fn my_letter(code: usize) -> char {
+    MY_LETTERS.chars().nth(code).unwrap_or('?')
+}
+
+// The opponent's letters count from A. Ours count from X and carry on from A
+// after Z, so a five-shape game's guide uses X, Y, Z, A and B.
+fn parse_guide(input: &str) -> Result<Vec<(usize, usize)>> {
     input
         .lines()
-        .map(|l| {
-            scores
-                .get(l)
-                .ok_or_else(|| format!("invalid game {}", l).into())
+        .map(|l| match l.as_bytes() {
+            [theirs @ b'A'..=b'Z', b' ', mine @ b'X'..=b'Z' | mine @ b'A'..=b'W'] => {
+                let mine = if *mine >= b'X' {
+                    mine - b'X'
+                } else {
+                    mine - b'A' + 3
+                };
+                Ok(((theirs - b'A') as usize, mine as usize))
+            }
+            _ => Err(format!("invalid game {l}").into()),
         })
-        .sum::<Result<i64, _>>()
-        .map(|sum| format!("{}", sum))
-}
-
-pub fn part1(input: &str) -> Result<String, Box<dyn Error>> {
-    let scores = [
-        ("A X", 3 + 1),
-        ("A Y", 6 + 2),
-        ("A Z", 0 + 3),
-        ("B X", 0 + 1),
-        ("B Y", 3 + 2),
-        ("B Z", 6 + 3),
-        ("C X", 6 + 1),
-        ("C Y", 0 + 2),
-        ("C Z", 3 + 3),
-    ];
-    summarize_human_readable(input, &scores)
-}
-
-pub fn part2(input: &str) -> Result<String, Box<dyn Error>> {
-    let scores = [
-        ("A X", 0 + 3),
-        ("A Y", 3 + 1),
-        ("A Z", 6 + 2),
-        ("B X", 0 + 1),
-        ("B Y", 3 + 2),
-        ("B Z", 6 + 3),
-        ("C X", 0 + 2),
-        ("C Y", 3 + 3),
-        ("C Z", 6 + 1),
-    ];
-    summarize_human_readable(input, &scores)
+        .collect()
+}
+
+/// Each round's score, with the running total.
+fn breakdown(rules: &Rules, rounds: &[Round]) -> Result<String> {
+    let mut out = String::new();
+    for (i, round) in rounds.iter().enumerate() {
+        writeln!(
+            out,
+            "{:>4}: {} vs {}, {:?}: {} + {} -> {}",
+            i + 1,
+            rules.shapes[round.mine].name,
+            rules.shapes[round.theirs].name,
+            round.outcome,
+            round.shape_points,
+            round.outcome_points,
+            round.running_total
+        )?;
+    }
+    Ok(out)
+}
+
+/// Scores the guide under both decodings, round by round, and reports which
+/// decoding scores best. `rules` is `classic`, `lizard-spock`, or a rules
+/// definition in the format accepted by `Rules::from_str`.
+pub fn report(input: &str, rules: &str) -> Result<String> {
+    let rules: Rules = match rules {
+        "classic" => CLASSIC.parse()?,
+        "lizard-spock" => LIZARD_SPOCK.parse()?,
+        rules => rules.parse()?,
+    };
+    let guide = parse_guide(input)?;
+
+    let mut out = String::new();
+    let as_shapes = Decoding::Shapes((0..rules.shapes.len()).collect());
+    for (title, decoding) in [
+        ("letters as shapes", as_shapes),
+        ("letters as outcomes", Decoding::Outcomes),
+    ] {
+        writeln!(out, "{title}:")?;
+        match rules.play(&guide, &decoding) {
+            Ok(rounds) => out.push_str(&breakdown(&rules, &rounds)?),
+            Err(e) => writeln!(out, "  {e}")?,
+        }
+    }
+
+    let (best, score) = rules.best_decoding(&guide)?;
+    let described = match best {
+        Decoding::Outcomes => "letters as outcomes".to_string(),
+        Decoding::Shapes(shapes) => shapes
+            .iter()
+            .zip(MY_LETTERS.chars())
+            .map(|(shape, letter)| format!("{letter} = {}", rules.shapes[*shape].name))
+            .collect::<Vec<_>>()
+            .join(", "),
+    };
+    writeln!(out, "best decoding: {described}, scoring {score}")?;
+    Ok(out)
+}
+
+pub fn part1(input: &str) -> Result<String> {
+    let rules: Rules = CLASSIC.parse()?;
+    let rounds = rules.play(&parse_guide(input)?, &Decoding::Shapes(vec![0, 1, 2]))?;
+    Ok(total(&rounds).to_string())
+}
+
+pub fn part2(input: &str) -> Result<String> {
+    let rules: Rules = CLASSIC.parse()?;
+    let rounds = rules.play(&parse_guide(input)?, &Decoding::Outcomes)?;
+    Ok(total(&rounds).to_string())
 }
 
 #[cfg(test)]
 mod test {
-    use super::part1;
-    use super::part2;
+    use super::*;
 
     const TEST_INPUT: &str = include_str!("tests/day2test.txt");
 
@@ -62,4 +325,56 @@ mod test {
     fn test_part2() {
         assert_eq!(part2(&TEST_INPUT).unwrap(), "12")
     }
+
+    #[test]
+    fn test_rules() {
+        let rules: Rules = LIZARD_SPOCK.parse().unwrap();
+        assert_eq!(rules.outcome(4, 0), Outcome::Win);
+        assert_eq!(rules.outcome(3, 4), Outcome::Win);
+        assert_eq!(rules.outcome(2, 4), Outcome::Lose);
+        assert_eq!(rules.shape_for(1, Outcome::Lose), Some(0));
+
+        assert!("rock 1 beats paper\npaper 2 beats rock"
+            .parse::<Rules>()
+            .is_err());
+        assert!("rock 1 beats stone".parse::<Rules>().is_err());
+        assert!("rock 1 beats".parse::<Rules>().is_ok());
+    }
+
+    #[test]
+    fn test_breakdown() {
+        let rules: Rules = CLASSIC.parse().unwrap();
+        let rounds = rules
+            .play(&parse_guide(TEST_INPUT).unwrap(), &Decoding::Outcomes)
+            .unwrap();
+        assert_eq!(
+            breakdown(&rules, &rounds).unwrap(),
+            "   1: rock vs rock, Draw: 1 + 3 -> 4
+   2: rock vs paper, Lose: 1 + 0 -> 5
+   3: rock vs scissors, Win: 1 + 6 -> 12
+"
+        );
+    }
+
+    #[test]
+    fn test_best_decoding() {
+        let rules: Rules = CLASSIC.parse().unwrap();
+        let guide = parse_guide(TEST_INPUT).unwrap();
+        // X = scissors, Y = paper, Z = rock wins every round
+        assert_eq!(
+            rules.best_decoding(&guide).unwrap(),
+            (Decoding::Shapes(vec![2, 1, 0]), 2 + 6 + 3 + 6 + 1 + 6)
+        );
+        assert_eq!(permutations(3).count(), 6);
+    }
+
+    #[test]
+    fn test_unknown_shape() {
+        for part in [part1, part2] {
+            assert_eq!(
+                part("A Y\nD X").unwrap_err().to_string(),
+                "can't read 'D' as a shape"
+            );
+        }
+    }
 }
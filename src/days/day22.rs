@@ -1,5 +1,10 @@
 use std::{collections::HashMap, error::Error, str::FromStr};
 
+use crate::{
+    record::{self, Frame},
+    sections::sections,
+};
+
 type Result<T> = std::result::Result<T, Box<dyn Error>>;
 
 #[derive(PartialEq, Eq, Clone, Copy, Debug)]
@@ -477,7 +482,47 @@ impl State {
     fn apply_moves(&mut self, moves: &[Move]) {
         for mv in moves {
             self.apply_move(mv);
+            record::frame(|| {
+                Frame::from_text(
+                    &self.render(),
+                    Some(format!(
+                        "{mv:?} -> {:?} facing {:?}",
+                        self.position, self.facing
+                    )),
+                )
+            });
+        }
+    }
+
+    // draw the map with the path walked so far, marked with the facing at each tile
+    fn render(&self) -> String {
+        let mut rows: Vec<Vec<char>> = self
+            .grid
+            .rows
+            .iter()
+            .map(|row| {
+                row.iter()
+                    .map(|t| match t {
+                        Tile::Void => ' ',
+                        Tile::Open => '.',
+                        Tile::Wall => '#',
+                    })
+                    .collect()
+            })
+            .collect();
+
+        for ((x, y), facing) in &self.history {
+            rows[*y as usize][*x as usize] = match facing {
+                Direction::Up => '^',
+                Direction::Down => 'v',
+                Direction::Left => '<',
+                Direction::Right => '>',
+            };
         }
+
+        rows.iter()
+            .map(|r| r.iter().collect::<String>() + "\n")
+            .collect()
     }
 
     fn apply_move(&mut self, mv: &Move) {
@@ -531,10 +576,11 @@ impl State {
 }
 
 fn parse_input(input: &str) -> Result<(Grid, Vec<Move>)> {
-    let (grid, moves) = input
-        .trim_end()
-        .split_once("\n\n")
-        .ok_or("couldn't split raw input".to_string())?;
+    let mut parts = sections(input);
+    let (Some(grid), Some(moves), None) = (parts.next(), parts.next(), parts.next()) else {
+        return Err("input must be a map and a line of moves".into());
+    };
+    let (grid, moves) = (grid.text, moves.text.trim());
 
     let moves: Vec<Move> = moves
         .split_inclusive(&['R', 'L'])
@@ -1,5 +1,4 @@
 use std::{
-    collections::HashSet,
     collections::VecDeque,
     error::Error,
     fmt::{Display, Formatter, Write},
@@ -8,6 +7,10 @@ use std::{
     str::FromStr,
 };
 
+use crate::bitgrid::BitGrid;
+use crate::grid::Location;
+use crate::record::{self, Frame};
+
 type Result<T> = std::result::Result<T, Box<dyn Error>>;
 
 #[derive(Default, Debug, Copy, Clone, Eq, PartialEq, Hash)]
@@ -80,24 +83,6 @@ where
     }
 }
 
-impl TryMove {
-    fn try_move(&self, elf: &Point, elves: &HashSet<Point>) -> Option<Point> {
-        if self.tests.iter().all(|test| !elves.contains(&(test + elf))) {
-            Some(self.mv + *elf)
-        } else {
-            None
-        }
-    }
-
-    fn find_move<'a>(
-        moves: impl IntoIterator<Item = &'a TryMove>,
-        elf: &Point,
-        elves: &HashSet<Point>,
-    ) -> Option<Point> {
-        moves.into_iter().find_map(|m| m.try_move(elf, elves))
-    }
-}
-
 impl Add<Point> for Point {
     type Output = Point;
 
@@ -127,12 +112,66 @@ impl Sub<Point> for Point {
     }
 }
 
-#[derive(Default)]
+// Free cells left around the elves whenever the grid is rebuilt.
+const MARGIN: i64 = 16;
+
+/// The elves' positions, packed into a grid with room around them to move.
+/// Rounds work on whole words of a row at a time.
 struct Field {
-    elves: HashSet<Point>,
+    elves: BitGrid,
+    // the position of the grid's top-left cell
+    origin: Point,
+    // the next round's positions, built while `elves` is read
+    next: BitGrid,
+    // the elves proposing each move this round, in the order they're tried
+    proposals: [BitGrid; 4],
+}
+
+// Word `w` of `row` in `grid`, as seen from `offset` away: bit i is set if
+// the cell at `offset` from column w * 64 + i is. Cells off the grid are
+// clear.
+fn seen_from(grid: &BitGrid, row: usize, w: usize, offset: Point) -> u64 {
+    let Some(words) = row
+        .checked_add_signed(offset.y as isize)
+        .filter(|r| *r < grid.height())
+        .map(|r| grid.row_words(r))
+    else {
+        return 0;
+    };
+    let word = |i: Option<usize>| i.and_then(|i| words.get(i)).copied().unwrap_or(0);
+    let shift = offset.x.unsigned_abs() as u32;
+    match offset.x {
+        0 => words[w],
+        1.. => (words[w] >> shift) | (word(w.checked_add(1)) << (u64::BITS - shift)),
+        _ => (words[w] << shift) | (word(w.checked_sub(1)) >> (u64::BITS - shift)),
+    }
 }
 
 impl Field {
+    fn contains(&self, p: Point) -> bool {
+        let (col, row) = (p.x - self.origin.x, p.y - self.origin.y);
+        col >= 0 && row >= 0 && self.elves.get(Location::new(row as usize, col as usize))
+    }
+
+    fn positions(&self) -> impl Iterator<Item = Point> + '_ {
+        self.elves
+            .ones()
+            .map(|loc| self.origin + Point::new(loc.col as i64, loc.row as i64))
+    }
+
+    // Whether some elf is on the edge of the grid, where it could move off.
+    fn at_edge(&self) -> bool {
+        let (width, height) = (self.elves.width(), self.elves.height());
+        width < 3
+            || height < 3
+            || self.elves.row_any(0)
+            || self.elves.row_any(height - 1)
+            || (0..height).any(|row| {
+                self.elves.get(Location::new(row, 0))
+                    || self.elves.get(Location::new(row, width - 1))
+            })
+    }
+
     fn step_n(&mut self, n: i64) {
         let mut moves = VecDeque::from(TRY_MOVES);
 
@@ -156,55 +195,61 @@ impl Field {
     }
 
     fn step(&mut self, moves: &VecDeque<TryMove>) -> i32 {
-        let mut elves: HashSet<Point> = HashSet::new();
-        let mut n_moved = 0;
-        for elf in &self.elves {
-            let new_elf = self.move_elf(*elf, &moves);
-            elves.insert(new_elf);
-            if *elf != new_elf {
-                n_moved += 1;
-            }
+        if self.at_edge() {
+            *self = self.positions().collect::<Vec<_>>().into_iter().collect();
         }
-
-        self.elves = elves;
-        n_moved
-    }
-
-    fn has_neighbors(&self, elf: Point) -> bool {
-        elf.neighbors().iter().any(|n| self.elves.contains(n))
-    }
-
-    fn move_elf(&self, elf: Point, moves: &VecDeque<TryMove>) -> Point {
-        if !self.has_neighbors(elf) {
-            return elf;
+        let Field {
+            elves,
+            next,
+            proposals,
+            ..
+        } = self;
+
+        // first half: every elf with company proposes the first free move
+        for row in 0..elves.height() {
+            for w in 0..elves.row_words(row).len() {
+                let crowded = Point::default()
+                    .neighbors()
+                    .iter()
+                    .fold(0, |acc, n| acc | seen_from(elves, row, w, *n));
+                let mut undecided = elves.row_words(row)[w] & crowded;
+                for (m, proposed) in moves.iter().zip(proposals.iter_mut()) {
+                    let blocked = m
+                        .tests
+                        .iter()
+                        .fold(0, |acc, t| acc | seen_from(elves, row, w, *t));
+                    proposed.row_words_mut(row)[w] = undecided & !blocked;
+                    undecided &= blocked;
+                }
+            }
         }
 
-        match TryMove::find_move(moves, &elf, &self.elves) {
-            Some(plan) if !self.is_collision(&elf, plan, moves) => plan,
-            _ => elf,
+        // second half: moves happen unless two elves propose the same cell,
+        // which only elves coming from opposite sides can do
+        let [north, south, west, east] = [NORTH, SOUTH, WEST, EAST]
+            .map(|mv| &proposals[moves.iter().position(|m| m.mv == mv).unwrap()]);
+        let mut n_moved = 0;
+        for row in 0..elves.height() {
+            for w in 0..elves.row_words(row).len() {
+                let arriving = (seen_from(north, row, w, SOUTH) ^ seen_from(south, row, w, NORTH))
+                    | (seen_from(west, row, w, EAST) ^ seen_from(east, row, w, WEST));
+                let leaving = (north.row_words(row)[w]
+                    & !seen_from(south, row, w, Point::new(0, -2)))
+                    | (south.row_words(row)[w] & !seen_from(north, row, w, Point::new(0, 2)))
+                    | (west.row_words(row)[w] & !seen_from(east, row, w, Point::new(-2, 0)))
+                    | (east.row_words(row)[w] & !seen_from(west, row, w, Point::new(2, 0)));
+                next.row_words_mut(row)[w] = (elves.row_words(row)[w] & !leaving) | arriving;
+                n_moved += leaving.count_ones() as i32;
+            }
         }
-    }
+        std::mem::swap(elves, next);
 
-    #[must_use]
-    fn is_collision(&self, elf: &Point, wants_move: Point, moves: &VecDeque<TryMove>) -> bool {
-        let probes = [
-            wants_move + NORTH,
-            wants_move + SOUTH,
-            wants_move + EAST,
-            wants_move + WEST,
-        ];
-
-        probes
-            .iter()
-            .filter(|p| *p != elf && self.elves.contains(p))
-            .any(|probe| {
-                self.has_neighbors(*probe)
-                    && TryMove::find_move(moves, probe, &self.elves) == Some(wants_move)
-            })
+        record::frame(|| Frame::from_text(&self.to_string(), Some(format!("{n_moved} moved"))));
+        n_moved
     }
 
     fn find_bounds(&self) -> (Point, Point) {
-        let (x_min, y_min, x_max, y_max) = self.elves.iter().fold(
+        let (x_min, y_min, x_max, y_max) = self.positions().fold(
             (i64::MAX, i64::MAX, i64::MIN, i64::MIN),
             |(x_min, y_min, x_max, y_max), pt| {
                 (
@@ -222,7 +267,31 @@ impl Field {
     fn find_free_area(&self) -> i64 {
         let (min, max) = self.find_bounds();
         let diff = max - min;
-        ((diff.x + 1) * (diff.y + 1)).abs() - self.elves.len() as i64
+        ((diff.x + 1) * (diff.y + 1)).abs() - self.elves.count_ones() as i64
+    }
+}
+
+impl FromIterator<Point> for Field {
+    fn from_iter<I: IntoIterator<Item = Point>>(iter: I) -> Self {
+        let points: Vec<Point> = iter.into_iter().collect();
+        let origin = Point::new(
+            points.iter().map(|p| p.x).min().unwrap_or(0) - MARGIN,
+            points.iter().map(|p| p.y).min().unwrap_or(0) - MARGIN,
+        );
+        let width = points.iter().map(|p| p.x - origin.x + 1).max().unwrap_or(0) + MARGIN;
+        let height = points.iter().map(|p| p.y - origin.y + 1).max().unwrap_or(0) + MARGIN;
+
+        let mut elves = BitGrid::new(width as usize, height as usize);
+        for p in points {
+            let at = p - origin;
+            elves.set(Location::new(at.y as usize, at.x as usize), true);
+        }
+        Field {
+            elves,
+            origin,
+            next: BitGrid::new(width as usize, height as usize),
+            proposals: std::array::from_fn(|_| BitGrid::new(width as usize, height as usize)),
+        }
     }
 }
 
@@ -230,20 +299,11 @@ impl FromStr for Field {
     type Err = Box<dyn Error>;
 
     fn from_str(s: &str) -> Result<Field> {
-        let elves: HashSet<Point> = s
-            .lines()
-            .enumerate()
-            .flat_map(|(y, l)| {
-                l.chars().enumerate().filter_map(move |(x, c)| {
-                    (c == '#').then(|| Point {
-                        x: x as i64,
-                        y: y as i64,
-                    })
-                })
-            })
-            .collect();
-
-        Ok(Field { elves })
+        let elves = BitGrid::from_rows(s.lines());
+        Ok(elves
+            .ones()
+            .map(|loc| Point::new(loc.col as i64, loc.row as i64))
+            .collect())
     }
 }
 
@@ -252,7 +312,7 @@ impl Display for Field {
         let bounds = self.find_bounds();
         for y in bounds.0.y..=bounds.1.y {
             for x in bounds.0.x..=bounds.1.x {
-                if self.elves.contains(&Point { x, y }) {
+                if self.contains(Point { x, y }) {
                     f.write_char('#')?;
                 } else {
                     f.write_char('.')?;
@@ -292,44 +352,54 @@ mod test {
     const INPUT: &str = include_str!("tests/day23test.txt");
 
     #[test]
-    fn test_find_move_available() {
-        let moves = VecDeque::from(TRY_MOVES);
-        let elf = Point { x: 1, y: 1 };
-        let elves: HashSet<Point> = [elf].into();
-        assert_eq!(
-            TryMove::find_move(&moves, &elf, &elves),
-            Some(Point { x: 1, y: 0 })
-        );
+    fn test_rounds() {
+        // the small example from the puzzle
+        let mut field: Field = ".....\n..##.\n..#..\n.....\n..##.\n.....".parse().unwrap();
+        let mut moves = VecDeque::from(TRY_MOVES);
+        for (moved, after) in [
+            (3, "##\n..\n#.\n.#\n#.\n"),
+            (5, ".##.\n#...\n...#\n....\n.#..\n"),
+            (3, "..#..\n....#\n#....\n....#\n.....\n..#..\n"),
+        ] {
+            assert_eq!(field.step(&moves), moved);
+            assert_eq!(field.to_string(), after);
+            moves.rotate_left(1);
+        }
     }
 
     #[test]
-    fn test_find_move_second() {
-        let moves = VecDeque::from(TRY_MOVES);
-        let elf = Point { x: 1, y: 1 };
-        let neighbor = Point { x: 0, y: 0 };
-        let elves: HashSet<Point> = [elf, neighbor].into();
-        assert_eq!(
-            TryMove::find_move(&moves, &elf, &elves),
-            Some(Point { x: 1, y: 2 })
-        );
+    fn test_second_choice() {
+        // north is blocked by the elf to the northwest
+        let mut field: Field = [(1, 1).into(), (0, 0).into()].into_iter().collect();
+        field.step(&VecDeque::from(TRY_MOVES));
+        assert!(field.contains(Point { x: 1, y: 2 }));
+        assert!(field.contains(Point { x: 0, y: -1 }));
     }
 
     #[test]
-    fn test_find_move_bug() {
-        let moves = VecDeque::from(TRY_MOVES);
-        let elf = Point { x: 3, y: 3 };
-        let elves: HashSet<Point> = [
+    fn test_move_bug() {
+        let mut field: Field = [
             (2, 0).into(),
             (3, 3).into(),
             (2, 2).into(),
             (3, 0).into(),
             (2, 4).into(),
         ]
-        .into();
+        .into_iter()
+        .collect();
 
+        assert_eq!(field.step(&VecDeque::from(TRY_MOVES)), 5);
+        let mut elves: Vec<Point> = field.positions().collect();
+        elves.sort_by_key(|p| (p.y, p.x));
         assert_eq!(
-            TryMove::find_move(&moves, &elf, &elves),
-            Some(Point { x: 4, y: 3 })
+            elves,
+            vec![
+                (2, -1).into(),
+                (3, -1).into(),
+                (2, 1).into(),
+                (4, 3).into(),
+                (2, 5).into()
+            ]
         );
     }
 
@@ -348,13 +418,15 @@ mod test {
     fn test_elf_neighbors() {
         let elf = (0, 0).into();
 
+        let mut alone: Field = [elf].into_iter().collect();
+        assert_eq!(alone.step(&VecDeque::from(TRY_MOVES)), 0);
+
+        // two elves next to each other always move apart
         for mv in [
             NORTH, NORTHEAST, EAST, SOUTHEAST, SOUTH, SOUTHWEST, WEST, NORTHWEST,
         ] {
-            let field = Field {
-                elves: [elf, elf + mv].into(),
-            };
-            assert_eq!(field.has_neighbors(elf), true);
+            let mut field: Field = [elf, elf + mv].into_iter().collect();
+            assert_eq!(field.step(&VecDeque::from(TRY_MOVES)), 2);
         }
     }
 
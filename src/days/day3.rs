@@ -1,7 +1,43 @@
-use std::error::Error;
+use std::{
+    error::Error,
+    fmt::Display,
+    ops::{BitAnd, BitOr, Sub},
+};
 
-fn type_score(ch: char) -> Result<i64, Box<dyn Error>> {
-    println!("type_score {}", ch);
+type Result<T> = std::result::Result<T, Box<dyn Error>>;
+
+/// A set of item types, one bit per priority: `a`-`z` are bits 0-25 and
+/// `A`-`Z` bits 26-51.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ItemSet(u64);
+
+/// Something wrong with a rucksack or a group of them.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Diagnostic {
+    InvalidItem {
+        line: usize,
+        column: usize,
+        item: char,
+    },
+    OddLength {
+        line: usize,
+    },
+    /// `lines` share no item type (the compartments of one rucksack, or the
+    /// rucksacks of a group).
+    NoSharedItem {
+        lines: Vec<usize>,
+    },
+    MultipleSharedItems {
+        lines: Vec<usize>,
+        items: ItemSet,
+    },
+    IncompleteGroup {
+        lines: Vec<usize>,
+        size: usize,
+    },
+}
+
+fn type_score(ch: char) -> Result<i64> {
     if ch.is_ascii_uppercase() {
         Ok(ch as i64 - 65 + 27)
     } else if ch.is_ascii_lowercase() {
@@ -11,44 +47,245 @@ fn type_score(ch: char) -> Result<i64, Box<dyn Error>> {
     }
 }
 
-pub fn part1(input: &str) -> Result<String, Box<dyn Error>> {
-    input
-        .lines()
-        .try_fold(0, |acc, line| -> Result<i64, _> {
-            let scored: Vec<i64> = line.chars().map(type_score).collect::<Result<_, _>>()?;
-            let (p1, p2) = scored.split_at(scored.len() / 2);
-            p1.iter()
-                .find(|s| p2.contains(s))
-                .map(|s| acc + s)
-                .ok_or("invalid input - no duplicated item".into())
-        })
-        .map(|s| s.to_string())
+fn type_char(priority: i64) -> char {
+    match priority {
+        1..=26 => (b'a' + priority as u8 - 1) as char,
+        _ => (b'A' + priority as u8 - 27) as char,
+    }
+}
+
+impl ItemSet {
+    pub const ALL: Self = Self((1 << 52) - 1);
+
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn insert(&mut self, item: char) -> Result<()> {
+        self.0 |= 1 << (type_score(item)? - 1);
+        Ok(())
+    }
+
+    #[allow(dead_code)]
+    pub fn contains(&self, item: char) -> bool {
+        type_score(item).is_ok_and(|p| self.0 & 1 << (p - 1) != 0)
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.count_ones() as usize
+    }
+
+    #[allow(dead_code)]
+    pub fn is_empty(&self) -> bool {
+        self.0 == 0
+    }
+
+    pub fn union(self, other: Self) -> Self {
+        Self(self.0 | other.0)
+    }
+
+    pub fn intersection(self, other: Self) -> Self {
+        Self(self.0 & other.0)
+    }
+
+    pub fn difference(self, other: Self) -> Self {
+        Self(self.0 & !other.0)
+    }
+
+    /// The priorities of the items in the set, lowest first.
+    pub fn priorities(&self) -> impl Iterator<Item = i64> {
+        let bits = self.0;
+        (0..52).filter(move |b| bits & 1 << b != 0).map(|b| b + 1)
+    }
+
+    pub fn items(&self) -> impl Iterator<Item = char> {
+        self.priorities().map(type_char)
+    }
+}
+
+impl BitOr for ItemSet {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self {
+        self.union(rhs)
+    }
+}
+
+impl BitAnd for ItemSet {
+    type Output = Self;
+
+    fn bitand(self, rhs: Self) -> Self {
+        self.intersection(rhs)
+    }
+}
+
+impl Sub for ItemSet {
+    type Output = Self;
+
+    fn sub(self, rhs: Self) -> Self {
+        self.difference(rhs)
+    }
+}
+
+impl Display for ItemSet {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.items().try_for_each(|c| write!(f, "{c}"))
+    }
+}
+
+fn describe_lines(lines: &[usize]) -> String {
+    match lines {
+        [line] => format!("line {line}"),
+        _ => format!(
+            "lines {}",
+            lines
+                .iter()
+                .map(|l| l.to_string())
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+    }
+}
+
+impl Display for Diagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Diagnostic::InvalidItem { line, column, item } => {
+                write!(f, "line {line}, column {column}: invalid item '{item}'")
+            }
+            Diagnostic::OddLength { line } => write!(
+                f,
+                "line {line}: odd number of items can't be split into compartments"
+            ),
+            Diagnostic::NoSharedItem { lines } => {
+                write!(f, "{}: no shared item", describe_lines(lines))
+            }
+            Diagnostic::MultipleSharedItems { lines, items } => write!(
+                f,
+                "{}: {} shared items ({items})",
+                describe_lines(lines),
+                items.len()
+            ),
+            Diagnostic::IncompleteGroup { lines, size } => write!(
+                f,
+                "{}: incomplete group of {} (groups are {size})",
+                describe_lines(lines),
+                lines.len()
+            ),
+        }
+    }
+}
+
+fn item_set(
+    items: &str,
+    line: usize,
+    first_column: usize,
+) -> std::result::Result<ItemSet, Diagnostic> {
+    let mut set = ItemSet::new();
+    for (column, item) in (first_column..).zip(items.chars()) {
+        set.insert(item)
+            .map_err(|_| Diagnostic::InvalidItem { line, column, item })?;
+    }
+    Ok(set)
+}
+
+// The one item type in every set, or why there isn't one.
+fn shared_item(sets: &[ItemSet], lines: Vec<usize>) -> std::result::Result<i64, Diagnostic> {
+    let shared = sets.iter().fold(ItemSet::ALL, |acc, &s| acc & s);
+    match shared.len() {
+        0 => Err(Diagnostic::NoSharedItem { lines }),
+        1 => Ok(shared.priorities().next().unwrap()),
+        _ => Err(Diagnostic::MultipleSharedItems {
+            lines,
+            items: shared,
+        }),
+    }
+}
+
+fn rucksacks(input: &str) -> impl Iterator<Item = (usize, &str)> {
+    input.lines().map(str::trim).zip(1..).map(|(l, n)| (n, l))
 }
 
-pub fn part2(input: &str) -> Result<String, Box<dyn Error>> {
-    let lines: Vec<&str> = input.lines().collect();
+// Sums the priorities, or lists everything that went wrong.
+fn total(priorities: impl Iterator<Item = std::result::Result<i64, Diagnostic>>) -> Result<String> {
+    let mut sum = 0;
+    let mut problems = Vec::new();
+    for priority in priorities {
+        match priority {
+            Ok(p) => sum += p,
+            Err(d) => problems.push(d.to_string()),
+        }
+    }
 
-    lines
-        .chunks(3)
-        .try_fold(0, |acc, group| {
-            group[0]
-                .chars()
-                .find(|c| group.iter().all(|line| line.contains(*c)))
-                .ok_or("invalid input".into())
-                .and_then(type_score)
-                .map(|score| acc + score)
-        })
-        .map(|score| score.to_string())
+    if problems.is_empty() {
+        Ok(sum.to_string())
+    } else {
+        Err(problems.join("\n").into())
+    }
+}
+
+/// The priority of the item type in both compartments of each rucksack.
+pub fn compartment_priorities(
+    input: &str,
+) -> impl Iterator<Item = std::result::Result<i64, Diagnostic>> + '_ {
+    rucksacks(input).map(|(line, items)| {
+        // every valid item is one byte, so the halves can be split by bytes
+        item_set(items, line, 1)?;
+        if items.len() % 2 != 0 {
+            return Err(Diagnostic::OddLength { line });
+        }
+        let (first, second) = items.split_at(items.len() / 2);
+        let compartments = [
+            item_set(first, line, 1)?,
+            item_set(second, line, first.len() + 1)?,
+        ];
+        shared_item(&compartments, vec![line])
+    })
+}
+
+/// The priority of the badge carried by every elf in each group of
+/// `group_size` consecutive rucksacks.
+pub fn badge_priorities(
+    input: &str,
+    group_size: usize,
+) -> impl Iterator<Item = std::result::Result<i64, Diagnostic>> + '_ {
+    assert!(group_size > 0, "groups need at least one rucksack");
+    let all: Vec<(usize, &str)> = rucksacks(input).collect();
+    let groups: Vec<Vec<(usize, &str)>> = all.chunks(group_size).map(<[_]>::to_vec).collect();
+    groups.into_iter().map(move |group| {
+        let lines: Vec<usize> = group.iter().map(|(n, _)| *n).collect();
+        if group.len() < group_size {
+            return Err(Diagnostic::IncompleteGroup {
+                lines,
+                size: group_size,
+            });
+        }
+        let sets = group
+            .iter()
+            .map(|&(line, items)| item_set(items, line, 1))
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+        shared_item(&sets, lines)
+    })
+}
+
+pub fn part1(input: &str) -> Result<String> {
+    total(compartment_priorities(input))
+}
+
+pub fn part2(input: &str) -> Result<String> {
+    total(badge_priorities(input, 3))
 }
 
 #[cfg(test)]
 mod test {
-    use super::part1;
-    use super::part2;
-    use super::type_score;
+    use super::*;
 
     const INPUT: &str = include_str!("tests/day3test.txt");
 
+    fn set(items: &str) -> ItemSet {
+        item_set(items, 1, 1).unwrap()
+    }
+
     #[test]
     fn test_part1_scores() {
         assert_eq!(type_score('a').unwrap(), 1);
@@ -56,6 +293,8 @@ mod test {
         assert_eq!(type_score('A').unwrap(), 27);
         assert_eq!(type_score('Z').unwrap(), 52);
         assert!(type_score('!').is_err());
+        assert_eq!(type_char(1), 'a');
+        assert_eq!(type_char(52), 'Z');
     }
 
     #[test]
@@ -65,7 +304,67 @@ mod test {
 
     #[test]
     fn test_part2() {
-        println!("part 2");
         assert_eq!(part2(INPUT).unwrap(), "70");
     }
+
+    #[test]
+    fn test_item_set() {
+        let a = set("abcXYZ");
+        let b = set("cdZ");
+        assert_eq!((a | b).to_string(), "abcdXYZ");
+        assert_eq!((a & b).to_string(), "cZ");
+        assert_eq!((a - b).to_string(), "abXY");
+        assert_eq!(a.len(), 6);
+        assert!(a.contains('X') && !a.contains('x') && !a.contains('!'));
+        assert_eq!((a & set("q")).priorities().count(), 0);
+    }
+
+    #[test]
+    fn test_group_size() {
+        assert_eq!(
+            total(badge_priorities(INPUT, 6)).unwrap_err().to_string(),
+            "lines 1, 2, 3, 4, 5, 6: no shared item"
+        );
+        assert_eq!(
+            badge_priorities(INPUT, 3).collect::<Vec<_>>(),
+            vec![Ok(18), Ok(52)]
+        );
+    }
+
+    #[test]
+    fn test_diagnostics() {
+        let input = "abca\nab!b\nabcab\nabab\nAxAx";
+        assert_eq!(
+            part1(input).unwrap_err().to_string(),
+            [
+                "line 2, column 3: invalid item '!'",
+                "line 3: odd number of items can't be split into compartments",
+                "line 4: 2 shared items (ab)",
+                "line 5: 2 shared items (xA)",
+            ]
+            .join("\n")
+        );
+        assert_eq!(
+            part2(input).unwrap_err().to_string(),
+            [
+                "line 2, column 3: invalid item '!'",
+                "lines 4, 5: incomplete group of 2 (groups are 3)",
+            ]
+            .join("\n")
+        );
+        assert_eq!(
+            total(badge_priorities("ab\ncd", 2))
+                .unwrap_err()
+                .to_string(),
+            "lines 1, 2: no shared item"
+        );
+        assert_eq!(
+            part1("aéb\naébc").unwrap_err().to_string(),
+            [
+                "line 1, column 2: invalid item 'é'",
+                "line 2, column 2: invalid item 'é'",
+            ]
+            .join("\n")
+        );
+    }
 }
@@ -1,52 +1,201 @@
-use std::{error::Error, ops::RangeInclusive};
+use std::{collections::BTreeMap, error::Error, fmt::Write, ops::RangeInclusive};
 
-fn parse_range(r: &str) -> Result<RangeInclusive<i64>, Box<dyn Error>> {
+use crate::interval::IntervalSet;
+
+type Result<T> = std::result::Result<T, Box<dyn Error>>;
+
+/// How many elves are assigned each section.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Coverage {
+    // consecutive runs of sections with the same depth, from the lowest
+    // assigned section to the highest, including runs nobody covers
+    runs: Vec<(RangeInclusive<i64>, usize)>,
+    covered: IntervalSet<i64>,
+}
+
+fn parse_range(r: &str) -> Result<RangeInclusive<i64>> {
     let parts = r.split_once('-').ok_or(format!("not a range: {}", r))?;
     Ok(parts.0.parse()?..=parts.1.parse()?)
 }
 
-fn parse_line(l: &str) -> Result<(RangeInclusive<i64>, RangeInclusive<i64>), Box<dyn Error>> {
-    let parts = l.split_once(',').ok_or(format!("not a pair: {}", l))?;
-    Ok((parse_range(parts.0)?, parse_range(parts.1)?))
+fn parse_group(l: &str) -> Result<Vec<RangeInclusive<i64>>> {
+    l.trim().split(',').map(parse_range).collect()
+}
+
+// Every line's assignments; a line can hold any number of elves.
+fn groups(input: &str) -> impl Iterator<Item = Result<Vec<RangeInclusive<i64>>>> + '_ {
+    input
+        .lines()
+        .zip(1..)
+        .filter(|(l, _)| !l.trim().is_empty())
+        .map(|(l, n)| parse_group(l).map_err(|e| format!("line {n}: {e}").into()))
+}
+
+/// The sections two assignments share, if any.
+pub fn overlap(r1: &RangeInclusive<i64>, r2: &RangeInclusive<i64>) -> Option<RangeInclusive<i64>> {
+    IntervalSet::from(r1.clone())
+        .intersection(&IntervalSet::from(r2.clone()))
+        .hull()
+}
+
+/// The sections every assignment in the group shares, if any.
+pub fn common(group: &[RangeInclusive<i64>]) -> Option<RangeInclusive<i64>> {
+    let (first, rest) = group.split_first()?;
+    rest.iter()
+        .try_fold(first.clone(), |shared, r| overlap(&shared, r))
+}
+
+// One elf's assignment holds everyone else's.
+fn one_contains_all(group: &[RangeInclusive<i64>]) -> bool {
+    group.iter().any(|r| {
+        group
+            .iter()
+            .all(|other| IntervalSet::from(r.clone()).covers(other))
+    })
+}
+
+fn all_overlap(group: &[RangeInclusive<i64>]) -> bool {
+    common(group).is_some()
+}
+
+impl Coverage {
+    pub fn new<'a>(assignments: impl IntoIterator<Item = &'a RangeInclusive<i64>>) -> Self {
+        // how the depth changes at each section
+        let mut changes: BTreeMap<i64, i64> = BTreeMap::new();
+        let mut covered = IntervalSet::new();
+        for r in assignments.into_iter().filter(|r| !r.is_empty()) {
+            *changes.entry(*r.start()).or_default() += 1;
+            *changes.entry(r.end() + 1).or_default() -= 1;
+            covered.insert(r.clone());
+        }
+
+        let mut runs: Vec<(RangeInclusive<i64>, usize)> = Vec::new();
+        let mut depth = 0;
+        let mut changes = changes.into_iter().peekable();
+        while let Some((start, change)) = changes.next() {
+            depth += change;
+            let Some(&(next, _)) = changes.peek() else {
+                break;
+            };
+            match runs.last_mut() {
+                // one elf's assignment ends where another's begins
+                Some((run, d)) if *d == depth as usize => *run = *run.start()..=next - 1,
+                _ => runs.push((start..=next - 1, depth as usize)),
+            }
+        }
+        Self { runs, covered }
+    }
+
+    /// The number of elves assigned `section`.
+    #[allow(dead_code)]
+    pub fn depth(&self, section: i64) -> usize {
+        let i = self.runs.partition_point(|(r, _)| *r.end() < section);
+        match self.runs.get(i) {
+            Some((r, depth)) if r.contains(&section) => *depth,
+            _ => 0,
+        }
+    }
+
+    /// Every section from the lowest assigned to the highest, with its depth.
+    #[allow(dead_code)]
+    pub fn sections(&self) -> impl Iterator<Item = (i64, usize)> + '_ {
+        self.runs
+            .iter()
+            .flat_map(|(r, depth)| r.clone().map(move |s| (s, *depth)))
+    }
+
+    /// Runs of consecutive sections with the same depth.
+    pub fn runs(&self) -> impl Iterator<Item = (RangeInclusive<i64>, usize)> + '_ {
+        self.runs.iter().cloned()
+    }
+
+    pub fn max_depth(&self) -> usize {
+        self.runs.iter().map(|(_, d)| *d).max().unwrap_or(0)
+    }
+
+    /// The sections assigned to the most elves.
+    pub fn deepest(&self) -> IntervalSet<i64> {
+        let max = self.max_depth();
+        self.runs
+            .iter()
+            .filter(|(_, d)| *d == max && max > 0)
+            .map(|(r, _)| r.clone())
+            .collect()
+    }
+
+    /// The sections in `bounds` nobody is assigned.
+    pub fn uncovered(&self, bounds: RangeInclusive<i64>) -> IntervalSet<i64> {
+        self.covered.complement(bounds)
+    }
+}
+
+fn format_range(r: &RangeInclusive<i64>) -> String {
+    format!("{}-{}", r.start(), r.end())
 }
 
-fn intersects(r1: &RangeInclusive<i64>, r2: &RangeInclusive<i64>) -> bool {
-    (r1.contains(r2.start()) && r1.contains(r2.end()))
-        || (r2.contains(r1.start()) && r2.contains(r1.end()))
+fn format_set(set: &IntervalSet<i64>) -> String {
+    if set.is_empty() {
+        return "none".to_string();
+    }
+    set.iter()
+        .map(|r| format_range(&r))
+        .collect::<Vec<_>>()
+        .join(", ")
 }
 
-fn overlaps(r1: &RangeInclusive<i64>, r2: &RangeInclusive<i64>) -> bool {
-    r1.contains(r2.start())
-        || r1.contains(r2.end())
-        || r2.contains(r1.start())
-        || r2.contains(r1.end())
+/// The sections each line's elves share, then how the whole camp is covered.
+/// Uncovered sections are counted from section 1 to the highest assigned.
+pub fn report(input: &str) -> Result<String> {
+    let groups = groups(input).collect::<Result<Vec<_>>>()?;
+    let coverage = Coverage::new(groups.iter().flatten());
+
+    let mut out = String::new();
+    writeln!(out, "assignments                shared")?;
+    for group in &groups {
+        let assignments: Vec<String> = group.iter().map(format_range).collect();
+        let shared = common(group).map_or("-".to_string(), |r| format_range(&r));
+        writeln!(out, "{:<26} {shared}", assignments.join(","))?;
+    }
+
+    writeln!(out, "\nsections  elves")?;
+    for (run, depth) in coverage.runs() {
+        writeln!(out, "{:<9} {depth}", format_range(&run))?;
+    }
+    writeln!(
+        out,
+        "\nmax depth {} at {}",
+        coverage.max_depth(),
+        format_set(&coverage.deepest())
+    )?;
+    let last = coverage.covered.hull().map_or(0, |h| *h.end());
+    writeln!(
+        out,
+        "uncovered: {}",
+        format_set(&coverage.uncovered(1..=last))
+    )?;
+    Ok(out)
 }
 
-pub fn check<F>(input: &str, f: F) -> Result<String, Box<dyn Error>>
+pub fn check<F>(input: &str, f: F) -> Result<String>
 where
-    F: Fn(&RangeInclusive<i64>, &RangeInclusive<i64>) -> bool,
+    F: Fn(&[RangeInclusive<i64>]) -> bool,
 {
-    input
-        .lines()
-        .try_fold(0, |acc, l| {
-            parse_line(l).and_then(|(r1, r2)| Ok(acc + f(&r1, &r2) as i64))
-        })
-        .map(|count| count.to_string())
+    groups(input)
+        .try_fold(0, |acc, group| Ok(acc + f(&group?) as i64))
+        .map(|count: i64| count.to_string())
 }
 
-pub fn part1(input: &str) -> Result<String, Box<dyn Error>> {
-    check(input, intersects)
+pub fn part1(input: &str) -> Result<String> {
+    check(input, one_contains_all)
 }
 
-pub fn part2(input: &str) -> Result<String, Box<dyn Error>> {
-    check(input, overlaps)
+pub fn part2(input: &str) -> Result<String> {
+    check(input, all_overlap)
 }
 
 #[cfg(test)]
 mod test {
-    use super::overlaps;
-    use super::part1;
-    use super::part2;
+    use super::*;
 
     const INPUT: &str = include_str!("tests/day4test.txt");
 
@@ -57,13 +206,66 @@ mod test {
 
     #[test]
     fn test_overlaps() {
-        assert!(overlaps(&(46..=95), &(68..=88)));
-        assert!(overlaps(&(41..=96), &(95..=97)));
-        assert!(overlaps(&(10..=10), &(9..=28)));
+        assert_eq!(overlap(&(46..=95), &(68..=88)), Some(68..=88));
+        assert_eq!(overlap(&(41..=96), &(95..=97)), Some(95..=96));
+        assert_eq!(overlap(&(10..=10), &(9..=28)), Some(10..=10));
+        assert_eq!(overlap(&(2..=4), &(6..=8)), None);
     }
 
     #[test]
     fn test_part2() {
         assert_eq!(part2(INPUT).unwrap(), "4");
     }
+
+    #[test]
+    fn test_groups() {
+        let input = "1-9,2-3,3-5\n2-4,3-6,4-8\n1-2,3-4,2-3";
+        assert_eq!(part1(input).unwrap(), "1");
+        assert_eq!(part2(input).unwrap(), "2");
+        assert_eq!(common(&[2..=4, 3..=6, 4..=8]), Some(4..=4));
+        assert_eq!(
+            part1("1-2,3-4\n5-x").unwrap_err().to_string(),
+            "line 2: invalid digit found in string"
+        );
+    }
+
+    #[test]
+    fn test_coverage() {
+        let groups = groups(INPUT).collect::<Result<Vec<_>>>().unwrap();
+        let coverage = Coverage::new(groups.iter().flatten());
+        assert_eq!(coverage.depth(1), 0);
+        assert_eq!(coverage.depth(4), 7);
+        assert_eq!(coverage.depth(9), 1);
+        assert_eq!(coverage.depth(10), 0);
+        assert_eq!(coverage.max_depth(), 8);
+        assert_eq!(coverage.deepest().iter().collect::<Vec<_>>(), vec![6..=6]);
+        assert_eq!(coverage.runs().nth(2), Some((4..=5, 7)));
+        assert_eq!(
+            coverage.sections().collect::<Vec<_>>(),
+            vec![
+                (2, 4),
+                (3, 5),
+                (4, 7),
+                (5, 7),
+                (6, 8),
+                (7, 6),
+                (8, 4),
+                (9, 1)
+            ]
+        );
+        assert_eq!(
+            coverage.uncovered(1..=12).iter().collect::<Vec<_>>(),
+            vec![1..=1, 10..=12]
+        );
+
+        let gappy = Coverage::new(&[1..=2, 5..=6]);
+        assert_eq!(
+            gappy.runs().collect::<Vec<_>>(),
+            vec![(1..=2, 1), (3..=4, 0), (5..=6, 1)]
+        );
+        assert_eq!(
+            gappy.uncovered(1..=6).iter().collect::<Vec<_>>(),
+            vec![3..=4]
+        );
+    }
 }
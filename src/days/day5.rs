@@ -1,74 +1,403 @@
-use std::{error::Error, str::FromStr};
+use std::{
+    error::Error,
+    fmt::{Display, Write},
+    str::FromStr,
+};
 
-#[derive(PartialEq, Debug)]
+use crate::sections::sections;
+
+#[derive(PartialEq, Debug, Clone)]
 struct Instruction {
     from: usize,
     to: usize,
     count: usize,
 }
 
-#[derive(PartialEq, Debug)]
+#[derive(PartialEq, Debug, Clone)]
 struct Instructions(Vec<Instruction>);
 
-#[derive(PartialEq, Debug)]
+#[derive(PartialEq, Debug, Clone)]
 struct Stacks(Vec<Vec<char>>);
 
-#[derive(PartialEq, Debug)]
+#[derive(PartialEq, Debug, Clone)]
 struct Input {
     stacks: Stacks,
     instructions: Instructions,
 }
 
-#[derive(PartialEq, Copy, Clone)]
-enum CrateGrouping {
-    Batch,
-    Single,
+/// How a crane sets down a load of crates it has lifted off a stack.
+pub trait Crane {
+    /// The order `count` lifted crates land in, bottom first. Each crate is
+    /// given by its position in the load, counting from the bottom.
+    fn arrange(&mut self, count: usize) -> Vec<usize>;
+}
+
+/// Moves one crate at a time, so a load lands upside down.
+pub struct CrateMover9000;
+
+/// Moves the whole load at once, keeping its order.
+pub struct CrateMover9001;
+
+/// Wraps another crane that can lift at most `limit` crates at once. Bigger
+/// loads are moved in lifts taken from the top.
+pub struct MaxLift<C> {
+    pub limit: usize,
+    pub crane: C,
+}
+
+/// Moves whole loads, but turns every other one upside down, starting with
+/// the first.
+#[derive(Default)]
+pub struct FlipAlternate {
+    lifts: usize,
+}
+
+impl Crane for CrateMover9000 {
+    fn arrange(&mut self, count: usize) -> Vec<usize> {
+        (0..count).rev().collect()
+    }
+}
+
+impl Crane for CrateMover9001 {
+    fn arrange(&mut self, count: usize) -> Vec<usize> {
+        (0..count).collect()
+    }
+}
+
+impl<C: Crane> Crane for MaxLift<C> {
+    fn arrange(&mut self, count: usize) -> Vec<usize> {
+        let mut order = Vec::with_capacity(count);
+        let mut left = count;
+        while left > 0 {
+            let lift = left.min(self.limit);
+            left -= lift;
+            order.extend(self.crane.arrange(lift).into_iter().map(|i| left + i));
+        }
+        order
+    }
+}
+
+impl Crane for FlipAlternate {
+    fn arrange(&mut self, count: usize) -> Vec<usize> {
+        self.lifts += 1;
+        if self.lifts % 2 == 1 {
+            CrateMover9000.arrange(count)
+        } else {
+            CrateMover9001.arrange(count)
+        }
+    }
+}
+
+/// A crane by name: `9000`, `9001` or `flip-alternate`, optionally limited
+/// to lifting `max_lift` crates at a time.
+pub fn crane(name: &str, max_lift: Option<usize>) -> Result<Box<dyn Crane>, Box<dyn Error>> {
+    fn limited<C: Crane + 'static>(crane: C, max_lift: Option<usize>) -> Box<dyn Crane> {
+        match max_lift {
+            Some(limit) => Box::new(MaxLift { limit, crane }),
+            None => Box::new(crane),
+        }
+    }
+
+    if max_lift == Some(0) {
+        return Err("a crane has to lift at least one crate".into());
+    }
+    Ok(match name {
+        "9000" => limited(CrateMover9000, max_lift),
+        "9001" => limited(CrateMover9001, max_lift),
+        "flip-alternate" => limited(FlipAlternate::default(), max_lift),
+        _ => return Err(format!("unknown crane '{name}'").into()),
+    })
 }
 
 impl Input {
-    fn process_instructions(self, order: CrateGrouping) -> Result<Stacks, Box<dyn Error>> {
-        let stacks = self.stacks;
-        self.instructions
-            .0
-            .iter()
-            .enumerate()
-            .try_fold(stacks, |s, (n, i)| {
-                s.process(i, order)
-                    .map_err(|e| format!("error '{}' on input line {}: {:?}", e, n, i).into())
-            })
+    fn process_instructions(mut self, crane: &mut dyn Crane) -> Result<Stacks, Box<dyn Error>> {
+        for (n, i) in self.instructions.0.iter().enumerate() {
+            self.stacks
+                .process(i, crane)
+                .map_err(|e| format!("error '{}' on input line {}: {:?}", e, n, i))?;
+        }
+        Ok(self.stacks)
+    }
+
+    // Recovers the starting stacks from the stacks the instructions ended
+    // with.
+    fn unwind_instructions(self, crane: &mut dyn Crane) -> Result<Stacks, Box<dyn Error>> {
+        let mut replay = Replay::finished(self, crane)?;
+        replay.seek(0)?;
+        Ok(replay.stacks)
     }
 }
 
 impl Stacks {
-    fn process(mut self, i: &Instruction, grouping: CrateGrouping) -> Result<Self, Box<dyn Error>> {
-        let src = self
-            .0
-            .get_mut(i.from - 1)
-            .ok_or_else(|| format!("invalid source {}", i.from))?;
-        let mut crates: Vec<char> = src[src.len() - i.count..src.len()].into();
-        if grouping == CrateGrouping::Single {
-            crates.reverse();
+    fn process(&mut self, i: &Instruction, crane: &mut dyn Crane) -> Result<(), Box<dyn Error>> {
+        move_heights(&mut self.heights(), i.from, i.to, i.count)?;
+        self.apply(i, &crane.arrange(i.count))
+    }
+
+    fn heights(&self) -> Vec<usize> {
+        self.0.iter().map(Vec::len).collect()
+    }
+
+    // Moves the crates for `i`, setting them down in `order` (see
+    // `Crane::arrange`).
+    fn apply(&mut self, i: &Instruction, order: &[usize]) -> Result<(), Box<dyn Error>> {
+        self.stack(i.to)?;
+        let load = self.take(i.from, i.count)?;
+        self.stack(i.to)?.extend(order.iter().map(|&n| load[n]));
+        Ok(())
+    }
+
+    // Takes back the crates `apply` moved, returning them to where they were.
+    fn undo(&mut self, i: &Instruction, order: &[usize]) -> Result<(), Box<dyn Error>> {
+        self.stack(i.from)?;
+        let landed = self.take(i.to, i.count)?;
+        let mut load = vec![' '; i.count];
+        for (&n, c) in order.iter().zip(landed) {
+            load[n] = c;
         }
-        src.truncate(src.len() - i.count);
+        self.stack(i.from)?.extend(load);
+        Ok(())
+    }
+
+    fn stack(&mut self, n: usize) -> Result<&mut Vec<char>, Box<dyn Error>> {
         self.0
-            .get_mut(i.to - 1)
-            .ok_or_else(|| format!("invalid dest {}", i.to))?
-            .extend_from_slice(&crates);
+            .get_mut(n.wrapping_sub(1))
+            .ok_or_else(|| format!("no stack {n}").into())
+    }
+
+    // Lifts the top `count` crates off stack `n`, bottom first.
+    fn take(&mut self, n: usize, count: usize) -> Result<Vec<char>, Box<dyn Error>> {
+        let stack = self.stack(n)?;
+        let Some(rest) = stack.len().checked_sub(count) else {
+            return Err(format!(
+                "can't take {count} crates from stack {n}, which has {}",
+                stack.len()
+            )
+            .into());
+        };
+        Ok(stack.split_off(rest))
+    }
+
+    fn top(&self, n: usize) -> Option<char> {
+        self.0.get(n.wrapping_sub(1))?.last().copied()
+    }
+
+    fn tops(&self) -> String {
+        self.0.iter().filter_map(|s| s.last()).collect()
+    }
+}
+
+// Moves `count` crates from stack `from` to stack `to` of stacks with these
+// heights, failing as `Stacks::take` would.
+fn move_heights(
+    heights: &mut [usize],
+    from: usize,
+    to: usize,
+    count: usize,
+) -> Result<(), Box<dyn Error>> {
+    let stack = |n: usize| -> Result<usize, Box<dyn Error>> {
+        n.checked_sub(1)
+            .filter(|&n| n < heights.len())
+            .ok_or_else(|| format!("no stack {n}").into())
+    };
+    let (from, to) = (stack(from)?, stack(to)?);
+    let Some(rest) = heights[from].checked_sub(count) else {
+        return Err(format!(
+            "can't take {count} crates from stack {}, which has {}",
+            from + 1,
+            heights[from]
+        )
+        .into());
+    };
+    heights[from] = rest;
+    heights[to] += count;
+    Ok(())
+}
+
+impl Display for Stacks {
+    // The drawing the stacks were parsed from: crates in columns four
+    // characters apart, every row padded to full width, then the labels.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let height = self.0.iter().map(Vec::len).max().unwrap_or(0);
+        for row in (0..height).rev() {
+            let cells: Vec<String> = self
+                .0
+                .iter()
+                .map(|s| s.get(row).map_or("   ".to_string(), |c| format!("[{c}]")))
+                .collect();
+            writeln!(f, "{}", cells.join(" "))?;
+        }
+        let labels: Vec<String> = (1..=self.0.len()).map(|n| format!(" {n} ")).collect();
+        write!(f, "{}", labels.join(" "))
+    }
+}
+
+impl Display for Instruction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "move {} from {} to {}", self.count, self.from, self.to)
+    }
+}
+
+/// Steps forwards and backwards through a run of instructions.
+struct Replay {
+    stacks: Stacks,
+    instructions: Vec<Instruction>,
+    // how the crane set down each instruction's crates
+    orders: Vec<Vec<usize>>,
+    // how many instructions have been applied to `stacks`
+    step: usize,
+}
+
+impl Replay {
+    // Where a crane moves crates depends only on how many it lifts and on
+    // what it has lifted before, so the whole run can be planned up front.
+    // Only the stacks' heights are followed, so that an instruction that
+    // couldn't be carried out is found before the crane is asked about it.
+    fn plan(
+        mut heights: Vec<usize>,
+        instructions: &[Instruction],
+        crane: &mut dyn Crane,
+    ) -> Result<Vec<Vec<usize>>, Box<dyn Error>> {
+        instructions
+            .iter()
+            .enumerate()
+            .map(|(n, i)| {
+                move_heights(&mut heights, i.from, i.to, i.count)
+                    .map_err(|e| format!("instruction {}: {i}: {e}", n + 1))?;
+                Ok(crane.arrange(i.count))
+            })
+            .collect()
+    }
+
+    fn new(input: Input, crane: &mut dyn Crane) -> Result<Self, Box<dyn Error>> {
+        let instructions = input.instructions.0;
+        Ok(Self {
+            orders: Self::plan(input.stacks.heights(), &instructions, crane)?,
+            stacks: input.stacks,
+            instructions,
+            step: 0,
+        })
+    }
+
+    // A replay positioned after the last instruction, for input whose stacks
+    // are the result of the run.
+    fn finished(input: Input, crane: &mut dyn Crane) -> Result<Self, Box<dyn Error>> {
+        let instructions = input.instructions.0;
+        let mut heights = input.stacks.heights();
+        for (n, i) in instructions.iter().enumerate().rev() {
+            move_heights(&mut heights, i.to, i.from, i.count)
+                .map_err(|e| format!("undoing instruction {}: {i}: {e}", n + 1))?;
+        }
+        Ok(Self {
+            orders: Self::plan(heights, &instructions, crane)?,
+            stacks: input.stacks,
+            step: instructions.len(),
+            instructions,
+        })
+    }
+
+    // Applies the next instruction, if there is one.
+    fn forward(&mut self) -> Result<Option<&Instruction>, Box<dyn Error>> {
+        let Some(i) = self.instructions.get(self.step) else {
+            return Ok(None);
+        };
+        self.stacks
+            .apply(i, &self.orders[self.step])
+            .map_err(|e| format!("instruction {}: {i}: {e}", self.step + 1))?;
+        self.step += 1;
+        Ok(Some(i))
+    }
+
+    // Undoes the last applied instruction, if there is one.
+    fn back(&mut self) -> Result<Option<&Instruction>, Box<dyn Error>> {
+        let Some(step) = self.step.checked_sub(1) else {
+            return Ok(None);
+        };
+        let i = &self.instructions[step];
+        self.stacks
+            .undo(i, &self.orders[step])
+            .map_err(|e| format!("undoing instruction {}: {i}: {e}", step + 1))?;
+        self.step = step;
+        Ok(Some(i))
+    }
+
+    fn seek(&mut self, step: usize) -> Result<(), Box<dyn Error>> {
+        if step > self.instructions.len() {
+            return Err(format!("there are only {} steps", self.instructions.len()).into());
+        }
+        while self.step < step {
+            self.forward()?;
+        }
+        while self.step > step {
+            self.back()?;
+        }
+        Ok(())
+    }
+
+    // The first step after which `item` is on top of `stack`; step 0 is the
+    // starting position.
+    fn first_on_top(&mut self, item: char, stack: usize) -> Result<Option<usize>, Box<dyn Error>> {
+        self.seek(0)?;
+        loop {
+            if self.stacks.top(stack) == Some(item) {
+                return Ok(Some(self.step));
+            }
+            if self.forward()?.is_none() {
+                return Ok(None);
+            }
+        }
+    }
+}
 
-        Ok(self)
+/// The stacks before the first instruction and after each one.
+pub fn simulate(input: &str, crane: &mut dyn Crane) -> Result<String, Box<dyn Error>> {
+    let mut replay = Replay::new(input.parse()?, crane)?;
+
+    let mut out = format!("{}\n", replay.stacks);
+    while let Some(i) = replay.forward()? {
+        let i = i.to_string();
+        writeln!(out, "\n{i}\n{}", replay.stacks)?;
     }
+    Ok(out)
+}
+
+/// The first step at which `item` is on top of stack `stack`.
+pub fn first_on_top(
+    input: &str,
+    item: char,
+    stack: usize,
+    crane: &mut dyn Crane,
+) -> Result<String, Box<dyn Error>> {
+    let mut replay = Replay::new(input.parse()?, crane)?;
+    Ok(match replay.first_on_top(item, stack)? {
+        Some(0) => format!("[{item}] starts on top of stack {stack}"),
+        Some(step) => format!(
+            "[{item}] first reaches the top of stack {stack} at step {step}: {}",
+            replay.instructions[step - 1]
+        ),
+        None => format!("[{item}] never reaches the top of stack {stack}"),
+    })
+}
+
+/// The stacks the instructions started from, taking the input's stacks as
+/// the ones they ended with.
+pub fn unwind(input: &str, crane: &mut dyn Crane) -> Result<String, Box<dyn Error>> {
+    let stacks = input.parse::<Input>()?.unwind_instructions(crane)?;
+    Ok(format!("{stacks}\n"))
 }
 
 impl FromStr for Input {
     type Err = Box<dyn Error>;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let (stacks, instructions) = s
-            .split_once("\n\n")
-            .ok_or("invalid input - must contain two sections".to_string())?;
+        let mut parts = sections(s);
+        let (Some(stacks), Some(instructions), None) = (parts.next(), parts.next(), parts.next())
+        else {
+            return Err("invalid input - must contain two sections".into());
+        };
         Ok(Self {
-            stacks: stacks.parse()?,
-            instructions: instructions.parse()?,
+            stacks: stacks.text.parse()?,
+            instructions: instructions.text.parse()?,
         })
     }
 }
@@ -90,7 +419,7 @@ impl FromStr for Stacks {
         let mut lines = s.lines().rev();
         let labels = lines.next().ok_or("invalid input - too short")?;
 
-        let mut stacks = vec![vec![]; labels.chars().filter(|c| c.is_numeric()).count()];
+        let mut stacks = vec![vec![]; labels.split_whitespace().count()];
 
         for line in lines {
             for (i, c) in line.char_indices().filter(|(_, c)| c.is_alphabetic()) {
@@ -119,16 +448,12 @@ impl FromStr for Instruction {
 
 pub fn part1(input: &str) -> Result<String, Box<dyn Error>> {
     let input: Input = input.parse()?;
-    let output = input.process_instructions(CrateGrouping::Single)?;
-
-    Ok(output.0.iter().filter_map(|s| s.last()).collect())
+    Ok(input.process_instructions(&mut CrateMover9000)?.tops())
 }
 
 pub fn part2(input: &str) -> Result<String, Box<dyn Error>> {
     let input: Input = input.parse()?;
-    let output = input.process_instructions(CrateGrouping::Batch)?;
-
-    Ok(output.0.iter().filter_map(|s| s.last()).collect())
+    Ok(input.process_instructions(&mut CrateMover9001)?.tops())
 }
 
 #[cfg(test)]
@@ -191,4 +516,154 @@ mod test {
     fn test_part2() {
         assert_eq!(part2(INPUT).unwrap(), "MCD")
     }
+
+    #[test]
+    fn test_cranes() {
+        assert_eq!(
+            MaxLift {
+                limit: 1,
+                crane: CrateMover9001
+            }
+            .arrange(3),
+            vec![2, 1, 0]
+        );
+        assert_eq!(
+            MaxLift {
+                limit: 2,
+                crane: CrateMover9001
+            }
+            .arrange(5),
+            vec![3, 4, 1, 2, 0]
+        );
+        let mut flip = FlipAlternate::default();
+        assert_eq!(flip.arrange(2), vec![1, 0]);
+        assert_eq!(flip.arrange(2), vec![0, 1]);
+        assert_eq!(flip.arrange(2), vec![1, 0]);
+
+        let run = |crane: &mut dyn Crane| {
+            let input: Input = INPUT.parse().unwrap();
+            input.process_instructions(crane).unwrap().tops()
+        };
+        assert_eq!(run(&mut *crane("9001", Some(1)).unwrap()), "CMZ");
+        assert_eq!(run(&mut *crane("9001", Some(2)).unwrap()), "MCZ");
+        assert_eq!(run(&mut *crane("flip-alternate", None).unwrap()), "CMD");
+        assert!(crane("9002", None).is_err());
+        assert!(crane("9000", Some(0)).is_err());
+    }
+
+    #[test]
+    fn test_display() {
+        let input: Input = INPUT.parse().unwrap();
+        let drawing = input.stacks.to_string();
+        assert_eq!(drawing, sections(INPUT).next().unwrap().text);
+        assert_eq!(drawing.parse::<Stacks>().unwrap(), input.stacks);
+
+        let wide = Stacks((1..=11).map(|n| vec!['A'; n % 3]).collect());
+        assert_eq!(wide.to_string().parse::<Stacks>().unwrap(), wide);
+    }
+
+    #[test]
+    fn test_simulate() {
+        let out = simulate(INPUT, &mut CrateMover9000).unwrap();
+        assert!(out.ends_with(
+            "move 1 from 1 to 2\n        [Z]\n        [N]\n        [D]\n[C] [M] [P]\n 1   2   3 \n"
+        ));
+        assert_eq!(
+            simulate(&INPUT.replace("move 3", "move 4"), &mut CrateMover9000)
+                .unwrap_err()
+                .to_string(),
+            "instruction 2: move 4 from 1 to 3: can't take 4 crates from stack 1, which has 3"
+        );
+
+        // found before any crane has to plan a load that size
+        let huge = INPUT.replace("move 1 from 2 to 1", "move 100000000000000 from 2 to 1");
+        for name in ["9000", "9001", "flip-alternate"] {
+            let crane = &mut *crane(name, None).unwrap();
+            assert_eq!(
+                simulate(&huge, crane).unwrap_err().to_string(),
+                "instruction 1: move 100000000000000 from 2 to 1: \
+                 can't take 100000000000000 crates from stack 2, which has 3"
+            );
+            assert_eq!(
+                huge.parse::<Input>()
+                    .unwrap()
+                    .process_instructions(crane)
+                    .unwrap_err()
+                    .to_string(),
+                "error 'can't take 100000000000000 crates from stack 2, which has 3' \
+                 on input line 0: Instruction { from: 2, to: 1, count: 100000000000000 }"
+            );
+        }
+    }
+
+    #[test]
+    fn test_replay() {
+        let mut replay = Replay::new(INPUT.parse().unwrap(), &mut CrateMover9000).unwrap();
+        replay.seek(4).unwrap();
+        assert_eq!(replay.stacks.tops(), "CMZ");
+        assert!(replay.forward().unwrap().is_none());
+
+        assert_eq!(
+            replay.back().unwrap().unwrap().to_string(),
+            "move 1 from 1 to 2"
+        );
+        replay.seek(1).unwrap();
+        assert_eq!(
+            replay.stacks,
+            Stacks(vec![vec!['Z', 'N', 'D'], vec!['M', 'C'], vec!['P']])
+        );
+        replay.seek(0).unwrap();
+        assert_eq!(replay.stacks, Input::from_str(INPUT).unwrap().stacks);
+        assert!(replay.back().unwrap().is_none());
+        assert!(replay.seek(5).is_err());
+
+        assert_eq!(replay.first_on_top('Z', 3).unwrap(), Some(2));
+        assert_eq!(replay.first_on_top('N', 1).unwrap(), Some(0));
+        assert_eq!(replay.first_on_top('M', 3).unwrap(), None);
+        assert_eq!(
+            first_on_top(INPUT, 'C', 1, &mut CrateMover9000).unwrap(),
+            "[C] first reaches the top of stack 1 at step 4: move 1 from 1 to 2"
+        );
+    }
+
+    #[test]
+    fn test_unwind() {
+        let input: Input = INPUT.parse().unwrap();
+        for name in ["9000", "9001", "flip-alternate"] {
+            for max_lift in [None, Some(2)] {
+                let crane = || crane(name, max_lift).unwrap();
+                let Input {
+                    stacks,
+                    instructions,
+                } = input.clone();
+                let end = Input {
+                    stacks,
+                    instructions: instructions.clone(),
+                }
+                .process_instructions(&mut *crane())
+                .unwrap();
+                let end = Input {
+                    stacks: end,
+                    instructions,
+                };
+                assert_eq!(
+                    end.unwind_instructions(&mut *crane()).unwrap(),
+                    input.stacks
+                );
+            }
+        }
+
+        let end = "        [Z]\n        [N]\n        [D]\n[C] [M] [P]\n 1   2   3 \n\n".to_string()
+            + INPUT.split_once("\n\n").unwrap().1;
+        assert_eq!(
+            unwind(&end, &mut CrateMover9000).unwrap(),
+            INPUT.split_once("\n\n").unwrap().0.to_string() + "\n"
+        );
+        assert_eq!(
+            unwind(INPUT, &mut CrateMover9000)
+                .unwrap_err()
+                .to_string(),
+            "undoing instruction 2: move 3 from 1 to 3: can't take 3 crates from stack 3, which has 1"
+        );
+    }
 }
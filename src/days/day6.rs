@@ -1,31 +1,133 @@
-use std::error::Error;
-
-fn find_boundary(input: &str, size: usize) -> Result<String, Box<dyn Error>> {
-    input
-        .chars()
-        .collect::<Vec<_>>()
-        .windows(size)
-        .enumerate()
-        .find_map(|(i, chs)| {
-            if chs
-                .iter()
-                .enumerate()
-                .all(|(i, c)| !chs[i + 1..].contains(c))
-            {
-                Some((i + size).to_string())
-            } else {
-                None
+use std::{
+    collections::VecDeque,
+    error::Error,
+    io::{BufRead, BufReader, Read},
+};
+
+/// Spots markers: runs of `size` consecutive bytes that are all different.
+/// Bytes are fed in one at a time and each costs the same however big the
+/// window is, so any byte alphabet and any length of stream will do.
+pub struct MarkerDetector {
+    size: usize,
+    window: VecDeque<u8>,
+    // how many times each byte value appears in the window
+    counts: [u32; 256],
+    // how many byte values appear in the window at all
+    distinct: usize,
+}
+
+impl MarkerDetector {
+    pub fn new(size: usize) -> Result<Self, Box<dyn Error>> {
+        if size == 0 {
+            return Err("a marker is at least one byte long".into());
+        }
+        Ok(Self {
+            size,
+            window: VecDeque::with_capacity(size + 1),
+            counts: [0; 256],
+            distinct: 0,
+        })
+    }
+
+    /// Feeds in the next byte, returning whether it completes a marker.
+    pub fn push(&mut self, byte: u8) -> bool {
+        self.window.push_back(byte);
+        self.counts[byte as usize] += 1;
+        if self.counts[byte as usize] == 1 {
+            self.distinct += 1;
+        }
+
+        if self.window.len() > self.size {
+            let old = self.window.pop_front().unwrap();
+            self.counts[old as usize] -= 1;
+            if self.counts[old as usize] == 0 {
+                self.distinct -= 1;
             }
+        }
+        self.distinct == self.size
+    }
+}
+
+/// The 1-based position of the last byte of every marker in `bytes`.
+pub fn markers(
+    bytes: impl IntoIterator<Item = u8>,
+    size: usize,
+) -> Result<impl Iterator<Item = u64>, Box<dyn Error>> {
+    let mut detector = MarkerDetector::new(size)?;
+    Ok((1..)
+        .zip(bytes)
+        .filter_map(move |(n, b)| detector.push(b).then_some(n)))
+}
+
+/// The same markers, read from `input`. Reading stops at the first error.
+pub fn read_markers(
+    input: impl Read,
+    size: usize,
+) -> Result<impl Iterator<Item = std::io::Result<u64>>, Box<dyn Error>> {
+    try_markers(BufReader::new(input).bytes(), size)
+}
+
+fn try_markers(
+    bytes: impl Iterator<Item = std::io::Result<u8>>,
+    size: usize,
+) -> Result<impl Iterator<Item = std::io::Result<u64>>, Box<dyn Error>> {
+    let mut detector = MarkerDetector::new(size)?;
+    Ok((1..)
+        .zip(bytes)
+        .filter_map(move |(n, b)| match b {
+            Ok(b) => detector.push(b).then_some(Ok(n)),
+            Err(e) => Some(Err(e)),
         })
-        .ok_or_else(|| format!("invalid input {}", input).into())
+        .scan(false, |failed, m| {
+            if *failed {
+                return None;
+            }
+            *failed = m.is_err();
+            Some(m)
+        }))
+}
+
+fn first_marker<E: Into<Box<dyn Error>>>(
+    mut markers: impl Iterator<Item = Result<u64, E>>,
+    size: usize,
+) -> Result<String, Box<dyn Error>> {
+    match markers.next() {
+        Some(n) => Ok(n.map_err(Into::into)?.to_string()),
+        None => Err(format!("no run of {size} distinct characters in input").into()),
+    }
+}
+
+// The puzzle's datastream is a single word: whitespace around it (such as
+// the final newline) isn't part of it, whether it is read or streamed.
+fn start_of_packet(input: &str, size: usize) -> Result<String, Box<dyn Error>> {
+    first_marker(
+        markers(input.trim().bytes(), size)?.map(Ok::<_, Box<dyn Error>>),
+        size,
+    )
+}
+
+fn stream_start_of_packet(input: &mut dyn BufRead, size: usize) -> Result<String, Box<dyn Error>> {
+    let datastream = input
+        .bytes()
+        .skip_while(|b| b.as_ref().is_ok_and(u8::is_ascii_whitespace))
+        .take_while(|b| b.as_ref().map_or(true, |b| !b.is_ascii_whitespace()));
+    first_marker(try_markers(datastream, size)?, size)
 }
 
 pub fn part1(input: &str) -> Result<String, Box<dyn Error>> {
-    find_boundary(input, 4)
+    start_of_packet(input, 4)
 }
 
 pub fn part2(input: &str) -> Result<String, Box<dyn Error>> {
-    find_boundary(input, 14)
+    start_of_packet(input, 14)
+}
+
+pub fn part1_stream(input: &mut dyn BufRead) -> Result<String, Box<dyn Error>> {
+    stream_start_of_packet(input, 4)
+}
+
+pub fn part2_stream(input: &mut dyn BufRead) -> Result<String, Box<dyn Error>> {
+    stream_start_of_packet(input, 14)
 }
 
 #[cfg(test)]
@@ -49,4 +151,61 @@ mod test {
         assert_eq!(part2("nznrnfrfntjfmvfwmzdfjlvtqnbhcprsg").unwrap(), "29");
         assert_eq!(part2("zcfzfwzzqfrljwzlrfnpqdbhtmscgvjw").unwrap(), "26");
     }
+
+    #[test]
+    fn test_stream() {
+        let input = "mjqjpqmgbljsphdztnvjfqwrcgsmlb";
+        assert_eq!(part1_stream(&mut input.as_bytes()).unwrap(), "7");
+        assert_eq!(part2_stream(&mut input.as_bytes()).unwrap(), "19");
+        assert!(part1_stream(&mut "abcabc".as_bytes()).is_err());
+
+        // surrounding whitespace isn't part of the datastream either way
+        for input in ["abcabc\n", "  abcabc \n\n"] {
+            assert!(part1(input).is_err());
+            assert!(part1_stream(&mut input.as_bytes()).is_err());
+        }
+        let input = "\nabcd\n";
+        assert_eq!(part1(input).unwrap(), "4");
+        assert_eq!(part1_stream(&mut input.as_bytes()).unwrap(), "4");
+    }
+
+    #[test]
+    fn test_empty_marker() {
+        assert!(markers("abc".bytes(), 0).is_err());
+        assert_eq!(
+            read_markers("abc".as_bytes(), 0).err().unwrap().to_string(),
+            "a marker is at least one byte long"
+        );
+    }
+
+    #[test]
+    fn test_all_markers() {
+        let input = "mjqjpqmgbljsphdztnvjfqwrcgsmlb";
+        assert_eq!(
+            markers(input.bytes(), 4)
+                .unwrap()
+                .take(5)
+                .collect::<Vec<_>>(),
+            vec![7, 8, 9, 10, 11]
+        );
+        assert_eq!(
+            markers("aabab".bytes(), 2).unwrap().collect::<Vec<_>>(),
+            vec![3, 4, 5]
+        );
+        assert_eq!(markers("aaa".bytes(), 1).unwrap().count(), 3);
+
+        // any byte values count as symbols
+        let bytes = [0u8, 255, 0, 7, 255, 200];
+        assert_eq!(
+            markers(bytes, 3).unwrap().collect::<Vec<_>>(),
+            vec![4, 5, 6]
+        );
+        assert_eq!(
+            read_markers(&bytes[..], 3)
+                .unwrap()
+                .collect::<std::io::Result<Vec<_>>>()
+                .unwrap(),
+            vec![4, 5, 6]
+        );
+    }
 }
@@ -1,66 +1,915 @@
-use std::{collections::HashMap, error::Error};
+use std::{
+    collections::{BTreeMap, HashMap},
+    error::Error,
+    fmt::Write,
+    fs::OpenOptions,
+    io::BufRead,
+    path::{Component, Path},
+    rc::Rc,
+    str::FromStr,
+};
+
+/// An index into a [`FileSystem`]'s nodes.
+pub type NodeId = usize;
+
+/// The directory tree a terminal transcript explored.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileSystem {
+    // the root is always node 0, and every node comes after its parent
+    nodes: Vec<Node>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Node {
+    name: String,
+    parent: Option<NodeId>,
+    kind: Kind,
+    // the file's size, or everything under the directory
+    size: i64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Kind {
+    File,
+    Dir {
+        entries: BTreeMap<String, NodeId>,
+        // whether `ls` has been run in it, so its entries are known
+        listed: bool,
+    },
+}
+
+// One line of `ls` output.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Entry {
+    Dir,
+    File(i64),
+}
+
+impl FileSystem {
+    pub const ROOT: NodeId = 0;
+
+    fn new() -> Self {
+        Self {
+            nodes: vec![Node {
+                name: "/".to_string(),
+                parent: None,
+                kind: Kind::Dir {
+                    entries: BTreeMap::new(),
+                    listed: false,
+                },
+                size: 0,
+            }],
+        }
+    }
+
+    fn entries(&self, dir: NodeId) -> Option<&BTreeMap<String, NodeId>> {
+        match &self.nodes[dir].kind {
+            Kind::Dir { entries, .. } => Some(entries),
+            Kind::File => None,
+        }
+    }
+
+    pub fn is_dir(&self, id: NodeId) -> bool {
+        self.entries(id).is_some()
+    }
+
+    /// The node's size: a file's own, or the total of everything below a
+    /// directory.
+    pub fn size(&self, id: NodeId) -> i64 {
+        self.nodes[id].size
+    }
+
+    /// The directory's entries in name order; nothing for a file.
+    pub fn children(&self, id: NodeId) -> impl Iterator<Item = NodeId> + '_ {
+        self.entries(id)
+            .into_iter()
+            .flat_map(|e| e.values().copied())
+    }
+
+    /// The node's absolute path, like `/a/e`.
+    pub fn path(&self, id: NodeId) -> String {
+        let mut names = Vec::new();
+        let mut node = id;
+        while let Some(parent) = self.nodes[node].parent {
+            names.push(self.nodes[node].name.as_str());
+            node = parent;
+        }
+        names.reverse();
+        format!("/{}", names.join("/"))
+    }
+
+    /// The node at an absolute path such as `/a/e`; `/` is the root.
+    pub fn lookup(&self, path: &str) -> Option<NodeId> {
+        path.strip_prefix('/')?
+            .split('/')
+            .filter(|name| !name.is_empty())
+            .try_fold(Self::ROOT, |dir, name| {
+                self.entries(dir)?.get(name).copied()
+            })
+    }
+
+    /// Every directory's path and total size, deepest first as `du` prints
+    /// them, ending with the root.
+    pub fn du(&self) -> Vec<(String, i64)> {
+        fn visit(fs: &FileSystem, dir: NodeId, out: &mut Vec<(String, i64)>) {
+            for child in fs.children(dir).filter(|c| fs.is_dir(*c)) {
+                visit(fs, child, out);
+            }
+            out.push((fs.path(dir), fs.size(dir)));
+        }
+
+        let mut out = Vec::new();
+        visit(self, Self::ROOT, &mut out);
+        out
+    }
+
+    /// The tree below `id`, drawn the way the puzzle does:
+    ///
+    /// ```text
+    /// - / (dir)
+    ///   - a (dir)
+    ///     - i (file, size=584)
+    /// ```
+    pub fn tree(&self, id: NodeId) -> String {
+        fn draw(fs: &FileSystem, id: NodeId, depth: usize, out: &mut String) {
+            let node = &fs.nodes[id];
+            let kind = match node.kind {
+                Kind::Dir { .. } => "dir".to_string(),
+                Kind::File => format!("file, size={}", node.size),
+            };
+            // writing to a String can't fail
+            let _ = writeln!(
+                out,
+                "{:indent$}- {} ({kind})",
+                "",
+                node.name,
+                indent = depth * 2
+            );
+            for child in fs.children(id) {
+                draw(fs, child, depth + 1, out);
+            }
+        }
+
+        let mut out = String::new();
+        draw(self, id, 0, &mut out);
+        out
+    }
+
+    // The subdirectory `name` of `dir`, which a listing must have declared.
+    fn child_dir(&self, dir: NodeId, name: &str) -> Result<NodeId, String> {
+        match self.entries(dir).and_then(|e| e.get(name)) {
+            Some(&id) if self.is_dir(id) => Ok(id),
+            Some(_) => Err(format!(
+                "cd into {}, which is a file",
+                self.path_of(dir, name)
+            )),
+            None => Err(format!(
+                "cd into {}, which no listing declared",
+                self.path_of(dir, name)
+            )),
+        }
+    }
+
+    fn path_of(&self, dir: NodeId, name: &str) -> String {
+        match dir {
+            Self::ROOT => format!("/{name}"),
+            _ => format!("{}/{name}", self.path(dir)),
+        }
+    }
+
+    // Records what `ls` showed in `dir`. Listing a directory again is fine
+    // as long as it shows the same thing.
+    fn record(&mut self, dir: NodeId, listing: BTreeMap<String, Entry>) -> Result<(), String> {
+        let Kind::Dir { entries, listed } = &self.nodes[dir].kind else {
+            unreachable!("only directories can be current");
+        };
+        if *listed {
+            let before: BTreeMap<String, Entry> = entries
+                .iter()
+                .map(|(name, &id)| (name.clone(), self.entry(id)))
+                .collect();
+            if before != listing {
+                return Err(format!(
+                    "{} listed again with different contents",
+                    self.path(dir)
+                ));
+            }
+            return Ok(());
+        }
+
+        for (name, entry) in listing {
+            let (kind, size) = match entry {
+                Entry::Dir => (
+                    Kind::Dir {
+                        entries: BTreeMap::new(),
+                        listed: false,
+                    },
+                    0,
+                ),
+                Entry::File(size) => (Kind::File, size),
+            };
+            let id = self.nodes.len();
+            self.nodes.push(Node {
+                name: name.clone(),
+                parent: Some(dir),
+                kind,
+                size,
+            });
+            if let Kind::Dir { entries, .. } = &mut self.nodes[dir].kind {
+                entries.insert(name, id);
+            }
+        }
+        if let Kind::Dir { listed, .. } = &mut self.nodes[dir].kind {
+            *listed = true;
+        }
+        Ok(())
+    }
+
+    fn entry(&self, id: NodeId) -> Entry {
+        match self.nodes[id].kind {
+            Kind::Dir { .. } => Entry::Dir,
+            Kind::File => Entry::File(self.nodes[id].size),
+        }
+    }
+
+    // Adds each file's size to the directories above it. Children come
+    // after their parents, so going backwards finishes each directory before
+    // it is added to its own parent.
+    fn total_sizes(&mut self) {
+        for id in (1..self.nodes.len()).rev() {
+            let (size, parent) = (self.nodes[id].size, self.nodes[id].parent.unwrap());
+            self.nodes[parent].size += size;
+        }
+    }
+}
+
+impl FromStr for FileSystem {
+    type Err = Box<dyn Error>;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut fs = FileSystem::new();
+        let mut cwd = FileSystem::ROOT;
+        // the output of the `ls` being read, and the line it was run on
+        let mut listing: Option<(BTreeMap<String, Entry>, usize)> = None;
+
+        for (line, n) in s.lines().zip(1..) {
+            let fail = |e: String| -> Box<dyn Error> { format!("line {n}: {e}").into() };
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            let Some(command) = line.strip_prefix("$ ") else {
+                let Some((entries, _)) = listing.as_mut() else {
+                    return Err(fail(format!("output '{line}' without an ls")));
+                };
+                let (info, name) = line
+                    .split_once(' ')
+                    .ok_or_else(|| fail(format!("unhandled input line {line}")))?;
+                let entry = match info {
+                    "dir" => Entry::Dir,
+                    size => match size.parse().map_err(|e| fail(format!("{e}")))? {
+                        size if size < 0 => return Err(fail(format!("{name} has size {size}"))),
+                        size => Entry::File(size),
+                    },
+                };
+                // `cd` couldn't get back to these, and paths would be ambiguous
+                if name.is_empty() || name.contains('/') || name == "." || name == ".." {
+                    return Err(fail(format!("invalid name '{name}'")));
+                }
+                if entries.insert(name.to_string(), entry).is_some() {
+                    return Err(fail(format!("{name} listed twice")));
+                }
+                continue;
+            };
+
+            if let Some((entries, at)) = listing.take() {
+                fs.record(cwd, entries)
+                    .map_err(|e| format!("line {at}: {e}"))?;
+            }
+            cwd = match command.split_once(' ') {
+                None if command == "ls" => {
+                    listing = Some((BTreeMap::new(), n));
+                    cwd
+                }
+                Some(("cd", "/")) => FileSystem::ROOT,
+                Some(("cd", "..")) => fs.nodes[cwd]
+                    .parent
+                    .ok_or_else(|| fail("invalid cd .. from /".to_string()))?,
+                Some(("cd", name)) => fs.child_dir(cwd, name).map_err(fail)?,
+                _ => return Err(fail(format!("unknown command '{command}'"))),
+            };
+        }
+        if let Some((entries, at)) = listing {
+            fs.record(cwd, entries)
+                .map_err(|e| format!("line {at}: {e}"))?;
+        }
+
+        fs.total_sizes();
+        Ok(fs)
+    }
+}
+
+/// What a cleanup plan tries to keep small, once it frees enough space.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Objective {
+    /// Delete as few things as possible, then as few bytes. Every
+    /// combination of that many deletions is tried, so this is only quick
+    /// when a handful will do.
+    FewestDeletions,
+    /// Delete as few bytes as possible. This keeps one bit for every byte
+    /// count up to the amount to free, so 2 MiB to free 16 MiB, plus another
+    /// such bitset for each deletable directory on the way down the tree and
+    /// a map entry for each total some deletions can free. More than 16 MiB
+    /// to free is refused.
+    LeastBytes,
+}
+
+/// Settings for [`Cleanup::plan`].
+#[derive(Debug, Clone)]
+pub struct Cleanup {
+    pub capacity: i64,
+    /// How much space has to be free afterwards.
+    pub required: i64,
+    pub objective: Objective,
+    /// Whether single files may be deleted, not just whole directories.
+    pub files: bool,
+    /// Paths that must survive. Nothing inside them or above them can be
+    /// deleted.
+    pub protected: Vec<String>,
+}
+
+/// What to delete, and how full the disk is before and after.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Plan {
+    pub deletions: Vec<NodeId>,
+    pub freed: i64,
+    pub capacity: i64,
+    pub used_before: i64,
+    pub used_after: i64,
+}
+
+// The most bytes a least-bytes plan can need to free; see `LeastBytes`.
+const MAX_LEAST_BYTES: i64 = 1 << 24;
+
+// The nodes a partial plan deletes, shared between the plans built from it.
+#[derive(Debug)]
+enum Chain {
+    Empty,
+    One(NodeId),
+    Both(Rc<Chain>, Rc<Chain>),
+}
+
+// A partial plan: how many nodes it deletes, and which.
+type Choice = (usize, Rc<Chain>);
+
+// Finds the deletions that free the fewest bytes of at least `limit`, as a
+// subset sum over the tree. Nodes are visited depth first, and `sums` holds
+// every total that some deletions among the nodes already finished with
+// can free. Those deletions never include a node's ancestors, which finish
+// after it, or its descendants, which start after it, so the node can be
+// added to any of them.
+struct LeastBytes<'a> {
+    fs: &'a FileSystem,
+    deletable: &'a [bool],
+    limit: usize,
+    // bit n is set if deleting some nodes frees exactly n bytes, for n
+    // below the limit
+    sums: Vec<u64>,
+    // for each reachable total, the node whose deletion first reached it
+    first: HashMap<usize, NodeId>,
+    // bitsets from finished nodes, reused rather than allocated per node
+    spare: Vec<Vec<u64>>,
+    // the best complete plan: bytes freed, the last node deleted, and the
+    // total freed before it
+    best: Option<(usize, NodeId, usize)>,
+}
+
+impl Default for Cleanup {
+    fn default() -> Self {
+        Self {
+            capacity: CAPACITY,
+            required: REQUIRED,
+            objective: Objective::FewestDeletions,
+            files: false,
+            protected: Vec::new(),
+        }
+    }
+}
+
+impl Chain {
+    fn collect(&self, out: &mut Vec<NodeId>) {
+        match self {
+            Chain::Empty => {}
+            Chain::One(id) => out.push(*id),
+            Chain::Both(a, b) => {
+                a.collect(out);
+                b.collect(out);
+            }
+        }
+    }
+}
+
+impl Cleanup {
+    /// The deletions that free enough space, best by the objective.
+    /// Deleting a directory deletes everything in it, so no plan deletes
+    /// both a directory and something inside it.
+    pub fn plan(&self, fs: &FileSystem) -> Result<Plan, Box<dyn Error>> {
+        let used = fs.size(FileSystem::ROOT);
+        let to_free = self.required - (self.capacity - used);
+        let deletable = self.deletable(fs)?;
+
+        let (mut deletions, freed) = if to_free <= 0 {
+            (Vec::new(), 0)
+        } else if Self::most_freeable(fs, &deletable, FileSystem::ROOT) < to_free {
+            return Err(
+                format!("can't free {to_free} bytes without deleting protected files").into(),
+            );
+        } else {
+            match self.objective {
+                Objective::FewestDeletions => Self::fewest_deletions(fs, &deletable, to_free),
+                Objective::LeastBytes if to_free > MAX_LEAST_BYTES => {
+                    return Err(
+                        format!("{to_free} bytes is too much to plan by least bytes").into(),
+                    )
+                }
+                Objective::LeastBytes => LeastBytes::plan(fs, &deletable, to_free as usize),
+            }
+        };
+
+        deletions.sort_by_key(|&id| fs.path(id));
+        Ok(Plan {
+            deletions,
+            freed,
+            capacity: self.capacity,
+            used_before: used,
+            used_after: used - freed,
+        })
+    }
+
+    // Whether each node may be deleted: not the root, and not a protected
+    // path, inside one or above one.
+    fn deletable(&self, fs: &FileSystem) -> Result<Vec<bool>, Box<dyn Error>> {
+        let protected = self
+            .protected
+            .iter()
+            .map(|path| {
+                fs.lookup(path)
+                    .ok_or_else(|| format!("no such file to protect: {path}"))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let mut blocked = vec![false; fs.nodes.len()];
+        blocked[FileSystem::ROOT] = true;
+        for &id in &protected {
+            let mut node = Some(id);
+            while let Some(id) = node {
+                blocked[id] = true;
+                node = fs.nodes[id].parent;
+            }
+        }
+        // nodes come after their parents, so this reaches all the way down
+        let mut inside = vec![false; fs.nodes.len()];
+        for id in 1..fs.nodes.len() {
+            inside[id] = protected.contains(&id) || inside[fs.nodes[id].parent.unwrap()];
+        }
+
+        Ok((0..fs.nodes.len())
+            .map(|id| !blocked[id] && !inside[id] && (self.files || fs.is_dir(id)))
+            .collect())
+    }
+
+    // Deleting a directory frees at least as much as deleting any of the
+    // things inside it.
+    fn most_freeable(fs: &FileSystem, deletable: &[bool], id: NodeId) -> i64 {
+        if deletable[id] {
+            fs.size(id)
+        } else {
+            fs.children(id)
+                .map(|child| Self::most_freeable(fs, deletable, child))
+                .sum()
+        }
+    }
+
+    // Tries one deletion, then two, and so on. Something frees enough, so
+    // this finishes.
+    fn fewest_deletions(fs: &FileSystem, deletable: &[bool], to_free: i64) -> (Vec<NodeId>, i64) {
+        let (freed, (count, chain)) = (1..)
+            .find_map(|limit| {
+                let mut best = None;
+                Self::partial_plans(fs, deletable, FileSystem::ROOT, to_free, limit, &mut best);
+                best
+            })
+            .unwrap();
+
+        let mut deletions = Vec::with_capacity(count);
+        chain.collect(&mut deletions);
+        (deletions, freed)
+    }
+
+    // Every way of deleting at most `limit` things under `id` that frees
+    // less than `to_free`, by bytes freed, with the fewest deletions for
+    // each. Ways that free enough are complete plans, and go to `best`
+    // instead.
+    fn partial_plans(
+        fs: &FileSystem,
+        deletable: &[bool],
+        id: NodeId,
+        to_free: i64,
+        limit: usize,
+        best: &mut Option<(i64, Choice)>,
+    ) -> HashMap<i64, Choice> {
+        let mut plans = HashMap::from([(0, (0, Rc::new(Chain::Empty)))]);
+        for child in fs.children(id) {
+            let below = Self::partial_plans(fs, deletable, child, to_free, limit, best);
+            if below.len() == 1 {
+                continue;
+            }
+            let mut combined = HashMap::with_capacity(plans.len() * below.len());
+            for (&freed, (n, chain)) in &plans {
+                for (&more, (m, other)) in &below {
+                    if n + m > limit {
+                        continue;
+                    }
+                    let chain = match (&**chain, &**other) {
+                        (Chain::Empty, _) => other.clone(),
+                        (_, Chain::Empty) => chain.clone(),
+                        _ => Rc::new(Chain::Both(chain.clone(), other.clone())),
+                    };
+                    Self::offer(freed + more, (n + m, chain), to_free, &mut combined, best);
+                }
+            }
+            plans = combined;
+        }
+
+        if deletable[id] {
+            let choice = (1, Rc::new(Chain::One(id)));
+            Self::offer(fs.size(id), choice, to_free, &mut plans, best);
+        }
+        plans
+    }
+
+    // Keeps a partial plan if it's the best way found so far to free that
+    // much, or if it frees enough, keeps it as the best plan found so far.
+    fn offer(
+        freed: i64,
+        choice: Choice,
+        to_free: i64,
+        plans: &mut HashMap<i64, Choice>,
+        best: &mut Option<(i64, Choice)>,
+    ) {
+        if freed >= to_free {
+            if best
+                .as_ref()
+                .is_none_or(|(f, (n, _))| (choice.0, freed) < (*n, *f))
+            {
+                *best = Some((freed, choice));
+            }
+        } else if plans.get(&freed).is_none_or(|(n, _)| choice.0 < *n) {
+            plans.insert(freed, choice);
+        }
+    }
+}
+
+impl<'a> LeastBytes<'a> {
+    fn plan(fs: &'a FileSystem, deletable: &'a [bool], limit: usize) -> (Vec<NodeId>, i64) {
+        let mut search = Self {
+            fs,
+            deletable,
+            limit,
+            sums: vec![0; limit.div_ceil(64)],
+            first: HashMap::new(),
+            spare: Vec::new(),
+            best: None,
+        };
+        search.sums[0] = 1;
+        search.visit(FileSystem::ROOT);
+
+        // something frees enough, so there is a best plan
+        let (freed, last, mut rest) = search.best.unwrap();
+        let mut deletions = vec![last];
+        while rest > 0 {
+            let id = search.first[&rest];
+            deletions.push(id);
+            rest -= fs.size(id) as usize;
+        }
+        (deletions, freed as i64)
+    }
+
+    fn visit(&mut self, id: NodeId) {
+        let with_this = self.deletable[id].then(|| {
+            let size = self.fs.size(id) as usize;
+            if let Some(before) = self.lowest_from(self.limit.saturating_sub(size)) {
+                if self.best.is_none_or(|(freed, _, _)| size + before < freed) {
+                    self.best = Some((size + before, id, before));
+                }
+            }
+            self.shifted(size)
+        });
+
+        for child in self.fs.children(id) {
+            self.visit(child);
+        }
+
+        if let Some(with_this) = with_this {
+            for (i, (sums, &new)) in self.sums.iter_mut().zip(&with_this).enumerate() {
+                let mut added = new & !*sums;
+                *sums |= new;
+                while added != 0 {
+                    self.first
+                        .insert(i * 64 + added.trailing_zeros() as usize, id);
+                    added &= added - 1;
+                }
+            }
+            self.spare.push(with_this);
+        }
+    }
+
+    // The smallest reachable total of at least `from`.
+    fn lowest_from(&self, from: usize) -> Option<usize> {
+        let mut i = from / 64;
+        let mut word = *self.sums.get(i)? & (!0 << (from % 64));
+        while word == 0 {
+            i += 1;
+            word = *self.sums.get(i)?;
+        }
+        Some(i * 64 + word.trailing_zeros() as usize)
+    }
+
+    // The reachable totals plus `by`, where still below the limit.
+    fn shifted(&mut self, by: usize) -> Vec<u64> {
+        let (words, bits) = (by / 64, by % 64);
+        let mut out = self.spare.pop().unwrap_or_default();
+        out.clear();
+        out.resize(self.sums.len(), 0);
+        for (i, word) in out.iter_mut().enumerate().skip(words) {
+            *word = self.sums[i - words] << bits;
+            if bits > 0 && i > words {
+                *word |= self.sums[i - words - 1] >> (64 - bits);
+            }
+        }
+        if !self.limit.is_multiple_of(64) {
+            *out.last_mut().unwrap() &= (1 << (self.limit % 64)) - 1;
+        }
+        out
+    }
+}
+
+impl Plan {
+    /// The plan as text: usage before, each deletion, usage after.
+    pub fn describe(&self, fs: &FileSystem) -> String {
+        let usage = |used: i64| {
+            format!(
+                "{used} used of {}, {} free",
+                self.capacity,
+                self.capacity - used
+            )
+        };
+
+        let mut out = format!("before: {}\n", usage(self.used_before));
+        for &id in &self.deletions {
+            let kind = if fs.is_dir(id) { "dir" } else { "file" };
+            out += &format!("delete {} ({kind}, {})\n", fs.path(id), fs.size(id));
+        }
+        out += &format!(
+            "after:  {} ({} deleted, {} freed)\n",
+            usage(self.used_after),
+            self.deletions.len(),
+            self.freed
+        );
+        out
+    }
+}
+
+/// Creates `fs`'s directories and files under the existing directory `dir`.
+/// Files are sparse, so they take up little real space whatever their size.
+/// Nothing outside `dir` is touched and nothing already there is overwritten.
+pub fn materialize(fs: &FileSystem, dir: &Path) -> Result<(), Box<dyn Error>> {
+    fn create(fs: &FileSystem, id: NodeId, path: &Path) -> Result<(), Box<dyn Error>> {
+        for child in fs.children(id) {
+            let name = &fs.nodes[child].name;
+            // a name like `..`, `/etc` or `a/b` would lead out of `path`
+            let mut components = Path::new(name).components();
+            let (Some(Component::Normal(_)), None) = (components.next(), components.next()) else {
+                return Err(format!("{}: can't create '{name}'", path.display()).into());
+            };
+            let path = path.join(name);
+            if fs.is_dir(child) {
+                std::fs::create_dir(&path).map_err(|e| format!("{}: {e}", path.display()))?;
+                create(fs, child, &path)?;
+            } else {
+                OpenOptions::new()
+                    .write(true)
+                    .create_new(true)
+                    .open(&path)
+                    .and_then(|f| f.set_len(fs.size(child) as u64))
+                    .map_err(|e| format!("{}: {e}", path.display()))?;
+            }
+        }
+        Ok(())
+    }
+
+    create(fs, FileSystem::ROOT, dir)
+}
+
+/// A transcript of exploring the real directory `dir` with `cd` and `ls`,
+/// visiting entries in name order.
+pub fn transcript(dir: &Path) -> Result<String, Box<dyn Error>> {
+    fn explore(dir: &Path, out: &mut String) -> Result<(), Box<dyn Error>> {
+        let mut entries = Vec::new();
+        for entry in std::fs::read_dir(dir)? {
+            let entry = entry?;
+            let path = entry.path();
+            let name = entry
+                .file_name()
+                .into_string()
+                .map_err(|_| format!("{}: name isn't UTF-8", path.display()))?;
+            if name.contains(['\n', '\r']) || name.trim().is_empty() {
+                return Err(
+                    format!("{}: name can't be written in a transcript", path.display()).into(),
+                );
+            }
+            let meta = std::fs::symlink_metadata(&path)?;
+            let size = if meta.is_dir() {
+                None
+            } else if meta.is_file() {
+                Some(meta.len())
+            } else {
+                return Err(format!("{}: not a file or directory", path.display()).into());
+            };
+            entries.push((name, size));
+        }
+        entries.sort();
+
+        writeln!(out, "$ ls")?;
+        for (name, size) in &entries {
+            match size {
+                Some(size) => writeln!(out, "{size} {name}")?,
+                None => writeln!(out, "dir {name}")?,
+            }
+        }
+        for (name, _) in entries.iter().filter(|(_, size)| size.is_none()) {
+            writeln!(out, "$ cd {name}")?;
+            explore(&dir.join(name), out)?;
+            writeln!(out, "$ cd ..")?;
+        }
+        Ok(())
+    }
+
+    let mut out = "$ cd /\n".to_string();
+    explore(dir, &mut out)?;
+    Ok(out)
+}
 
 fn parse_dir_sizes(input: &str) -> Result<HashMap<String, i64>, Box<dyn Error>> {
-    let mut sizes: HashMap<String, i64> = HashMap::new();
+    let fs: FileSystem = input.parse()?;
+    Ok(fs.du().into_iter().collect())
+}
+
+// Reads the log a line at a time, keeping only the sizes of the directories
+// on the current path. Each directory's total is passed to `done` as the log
+// leaves it, ending with "/", which assumes every directory is listed once, as
+// it is in the puzzle's transcripts.
+fn stream_dir_sizes(
+    input: &mut dyn BufRead,
+    mut done: impl FnMut(i64),
+) -> Result<(), Box<dyn Error>> {
+    // the total so far of "/" and of each directory below it on the path
+    let mut path: Vec<i64> = vec![0];
+    let mut leave = |path: &mut Vec<i64>| {
+        let size = path.pop().unwrap();
+        *path.last_mut().unwrap() += size;
+        done(size);
+    };
 
-    let mut current_dir: Vec<&str> = vec![];
     for line in input.lines() {
-        let tokens: Vec<&str> = line.split(' ').collect();
-        match tokens.as_slice() {
-            &["$", "ls"] => {} // ignore,
-            &["dir", _] => {}  // ignore
-            &["$", "cd", "/"] => current_dir.truncate(0),
-            &["$", "cd", ".."] => {
-                if current_dir.pop().is_none() {
-                    return Err(format!("invalid cd .. from {:?}", current_dir).into());
+        let line = line?;
+        match line.split(' ').collect::<Vec<&str>>().as_slice() {
+            ["$", "ls"] | ["dir", _] => {}
+            ["$", "cd", "/"] => {
+                while path.len() > 1 {
+                    leave(&mut path);
                 }
             }
-            &["$", "cd", child] => current_dir.push(child),
-            &[size, _] => {
-                let size: i64 = size.parse()?;
-                let mut path: String = "/".to_string();
-                *sizes.entry(path.clone()).or_insert(0) += size;
-                for elem in &current_dir {
-                    path.push('/');
-                    path.push_str(elem);
-                    *sizes.entry(path.clone()).or_insert(0) += size;
+            ["$", "cd", ".."] => {
+                if path.len() == 1 {
+                    return Err("invalid cd .. from /".into());
                 }
+                leave(&mut path);
             }
-            _ => panic!("unhandled input line {}", line),
+            ["$", "cd", _] => path.push(0),
+            [size, _] => *path.last_mut().unwrap() += size.parse::<i64>()?,
+            _ => return Err(format!("unhandled input line {line}").into()),
         }
     }
 
-    Ok(sizes)
+    while path.len() > 1 {
+        leave(&mut path);
+    }
+    done(path[0]);
+    Ok(())
 }
 
-pub fn part1(input: &str) -> Result<String, Box<dyn Error>> {
-    let sizes = parse_dir_sizes(input)?;
-    Ok(sizes
-        .values()
-        .filter(|size| **size <= 100000)
-        .sum::<i64>()
-        .to_string())
+const SMALL_DIR: i64 = 100000;
+const CAPACITY: i64 = 70000000;
+const REQUIRED: i64 = 30000000;
+
+fn small_dirs_total(sizes: impl Iterator<Item = i64>) -> i64 {
+    sizes.filter(|size| *size <= SMALL_DIR).sum()
 }
 
-pub fn part2(input: &str) -> Result<String, Box<dyn Error>> {
-    let sizes = parse_dir_sizes(input)?;
-    let capacity = 70000000;
-    let required = 30000000;
-    let to_free = required - (capacity - sizes["/"]);
+// The space part 2 frees: nothing if there's room already, or else the
+// smallest of the directories below "/" that frees enough, as the default
+// `Cleanup` plan would. `sizes` ends with "/", which gives what's used.
+fn smallest_to_delete(mut sizes: Vec<i64>) -> Result<String, Box<dyn Error>> {
+    let used = sizes.pop().ok_or("no directories")?;
+    let to_free = REQUIRED - (CAPACITY - used);
+    if to_free <= 0 {
+        return Ok("0".to_string());
+    }
 
-    let deleted = sizes.values().filter(|size| **size > to_free).min();
+    let deleted = sizes.into_iter().filter(|size| *size >= to_free).min();
 
     deleted
         .map(|s| s.to_string())
         .ok_or_else(|| "couldn't find dir to delete".into())
 }
 
+pub fn part1(input: &str) -> Result<String, Box<dyn Error>> {
+    let sizes = parse_dir_sizes(input)?;
+    Ok(small_dirs_total(sizes.values().copied()).to_string())
+}
+
+pub fn part2(input: &str) -> Result<String, Box<dyn Error>> {
+    let fs: FileSystem = input.parse()?;
+    smallest_to_delete(fs.du().into_iter().map(|(_, size)| size).collect())
+}
+
+pub fn part1_stream(input: &mut dyn BufRead) -> Result<String, Box<dyn Error>> {
+    let mut total = 0;
+    stream_dir_sizes(input, |size| {
+        if size <= SMALL_DIR {
+            total += size
+        }
+    })?;
+    Ok(total.to_string())
+}
+
+/// Needs every directory's size before it can choose one, so this keeps one
+/// number per directory, though still nothing per file or per line.
+pub fn part2_stream(input: &mut dyn BufRead) -> Result<String, Box<dyn Error>> {
+    let mut sizes = Vec::new();
+    stream_dir_sizes(input, |size| sizes.push(size))?;
+    smallest_to_delete(sizes)
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
+    use std::{
+        fs::File,
+        path::PathBuf,
+        sync::atomic::{AtomicUsize, Ordering},
+    };
 
     const INPUT: &str = include_str!("tests/day7test.txt");
 
+    // A real directory tree built from a `FileSystem` under a fresh
+    // temporary directory, and removed again when dropped.
+    struct Sandbox {
+        root: PathBuf,
+    }
+
+    impl Sandbox {
+        fn new(fs: &FileSystem) -> Result<Self, Box<dyn Error>> {
+            static COUNT: AtomicUsize = AtomicUsize::new(0);
+            loop {
+                let n = COUNT.fetch_add(1, Ordering::Relaxed);
+                let root = std::env::temp_dir()
+                    .join(format!("adventofcode2022-day7-{}-{n}", std::process::id()));
+                match std::fs::create_dir(&root) {
+                    Ok(()) => {
+                        let sandbox = Self { root };
+                        materialize(fs, &sandbox.root)?;
+                        return Ok(sandbox);
+                    }
+                    Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => continue,
+                    Err(e) => return Err(e.into()),
+                }
+            }
+        }
+
+        fn root(&self) -> &Path {
+            &self.root
+        }
+    }
+
+    impl Drop for Sandbox {
+        fn drop(&mut self) {
+            // nothing to be done if it's already gone
+            let _ = std::fs::remove_dir_all(&self.root);
+        }
+    }
+
     #[test]
     fn test_part1() {
         assert_eq!(part1(INPUT).unwrap(), "95437")
@@ -70,4 +919,256 @@ mod test {
     fn test_part2() {
         assert_eq!(part2(INPUT).unwrap(), "24933642")
     }
+
+    #[test]
+    fn test_stream() {
+        assert_eq!(part1_stream(&mut INPUT.as_bytes()).unwrap(), "95437");
+        assert_eq!(part2_stream(&mut INPUT.as_bytes()).unwrap(), "24933642");
+
+        // plenty of room already
+        let roomy = "$ cd /\n$ ls\ndir a\n100 b.txt\n$ cd a\n$ ls\n50 c.txt";
+        assert_eq!(part2(roomy).unwrap(), "0");
+        assert_eq!(part2_stream(&mut roomy.as_bytes()).unwrap(), "0");
+        let fs: FileSystem = roomy.parse().unwrap();
+        assert_eq!(Cleanup::default().plan(&fs).unwrap().freed, 0);
+    }
+
+    #[test]
+    fn test_file_system() {
+        let fs: FileSystem = INPUT.parse().unwrap();
+        assert_eq!(
+            fs.tree(FileSystem::ROOT),
+            "- / (dir)
+  - a (dir)
+    - e (dir)
+      - i (file, size=584)
+    - f (file, size=29116)
+    - g (file, size=2557)
+    - h.lst (file, size=62596)
+  - b.txt (file, size=14848514)
+  - c.dat (file, size=8504156)
+  - d (dir)
+    - d.ext (file, size=5626152)
+    - d.log (file, size=8033020)
+    - j (file, size=4060174)
+    - k (file, size=7214296)
+"
+        );
+        assert_eq!(
+            fs.du(),
+            vec![
+                ("/a/e".to_string(), 584),
+                ("/a".to_string(), 94853),
+                ("/d".to_string(), 24933642),
+                ("/".to_string(), 48381165),
+            ]
+        );
+
+        let e = fs.lookup("/a/e").unwrap();
+        assert_eq!(fs.path(e), "/a/e");
+        assert_eq!(fs.size(e), 584);
+        assert_eq!(fs.lookup("/"), Some(FileSystem::ROOT));
+        assert_eq!(fs.lookup("/a/f").map(|f| fs.is_dir(f)), Some(false));
+        assert_eq!(fs.lookup("/a/x"), None);
+        assert_eq!(fs.lookup("/a/f/x"), None);
+    }
+
+    #[test]
+    fn test_inconsistencies() {
+        let error = |input: &str| input.parse::<FileSystem>().unwrap_err().to_string();
+        assert_eq!(
+            error("$ cd /\n$ ls\ndir a\n$ cd b"),
+            "line 4: cd into /b, which no listing declared"
+        );
+        assert_eq!(
+            error("$ ls\n1 a\n$ cd a"),
+            "line 3: cd into /a, which is a file"
+        );
+        assert_eq!(
+            error("$ ls\ndir a\n$ cd a\n$ ls\n1 x\n$ cd ..\n$ cd a\n$ ls\n2 x"),
+            "line 8: /a listed again with different contents"
+        );
+        assert_eq!(error("$ ls\n1 a\n2 a"), "line 3: a listed twice");
+        assert_eq!(error("1 a"), "line 1: output '1 a' without an ls");
+        assert_eq!(error("$ cd .."), "line 1: invalid cd .. from /");
+        assert_eq!(error("$ rm -rf /"), "line 1: unknown command 'rm -rf /'");
+        assert_eq!(error("$ ls\ndir .."), "line 2: invalid name '..'");
+        assert_eq!(error("$ ls\n1 ."), "line 2: invalid name '.'");
+        assert_eq!(error("$ ls\ndir a/b"), "line 2: invalid name 'a/b'");
+        assert_eq!(error("$ ls\n1 /etc"), "line 2: invalid name '/etc'");
+        assert_eq!(error("$ ls\n-5 a"), "line 2: a has size -5");
+
+        // the same listing twice is fine
+        let fs: FileSystem = "$ ls\n1 a b\n$ ls\n1 a b".parse().unwrap();
+        assert_eq!(fs.size(fs.lookup("/a b").unwrap()), 1);
+    }
+
+    #[test]
+    fn test_cleanup() {
+        let fs: FileSystem = INPUT.parse().unwrap();
+        let paths =
+            |plan: &Plan| -> Vec<String> { plan.deletions.iter().map(|&id| fs.path(id)).collect() };
+
+        let plan = Cleanup::default().plan(&fs).unwrap();
+        assert_eq!(paths(&plan), vec!["/d"]);
+        assert_eq!(plan.used_after, 48381165 - 24933642);
+
+        // a single file is enough, and frees less than /d
+        let least = Cleanup {
+            objective: Objective::LeastBytes,
+            files: true,
+            ..Cleanup::default()
+        };
+        assert_eq!(paths(&least.plan(&fs).unwrap()), vec!["/c.dat"]);
+
+        // without it, two files free less than any one thing that's enough
+        let least = Cleanup {
+            protected: vec!["/c.dat".to_string()],
+            ..least
+        };
+        let plan = least.plan(&fs).unwrap();
+        assert_eq!(paths(&plan), vec!["/d/d.ext", "/d/j"]);
+        assert_eq!(plan.freed, 5626152 + 4060174);
+        let fewest = Cleanup {
+            objective: Objective::FewestDeletions,
+            ..least.clone()
+        };
+        assert_eq!(paths(&fewest.plan(&fs).unwrap()), vec!["/b.txt"]);
+
+        let protected = Cleanup {
+            protected: vec!["/d/j".to_string(), "/c.dat".to_string()],
+            ..least.clone()
+        };
+        assert_eq!(
+            paths(&protected.plan(&fs).unwrap()),
+            vec!["/d/d.ext", "/d/k"]
+        );
+        let protected = Cleanup {
+            protected: vec!["/d".to_string(), "/c.dat".to_string(), "/b.txt".to_string()],
+            ..least.clone()
+        };
+        assert_eq!(
+            protected.plan(&fs).unwrap_err().to_string(),
+            "can't free 8381165 bytes without deleting protected files"
+        );
+        // the bitsets would get too big
+        let greedy = Cleanup {
+            required: 40000000,
+            ..least.clone()
+        };
+        assert_eq!(
+            greedy.plan(&fs).unwrap_err().to_string(),
+            "18381165 bytes is too much to plan by least bytes"
+        );
+        assert!(Cleanup {
+            protected: vec!["/x".to_string()],
+            ..least
+        }
+        .plan(&fs)
+        .is_err());
+
+        // nothing to do when there is room already
+        let roomy = Cleanup {
+            capacity: 100000000,
+            ..Cleanup::default()
+        };
+        let plan = roomy.plan(&fs).unwrap();
+        assert!(plan.deletions.is_empty());
+        assert_eq!(plan.used_after, plan.used_before);
+    }
+
+    // Builds the transcript's tree on disk, transcribes it back, and checks
+    // nothing changed on the way.
+    fn round_trip(input: &str) -> String {
+        let fs: FileSystem = input.parse().unwrap();
+        let sandbox = Sandbox::new(&fs).unwrap();
+        let replayed = transcript(sandbox.root()).unwrap();
+
+        let back: FileSystem = replayed.parse().unwrap();
+        assert_eq!(back.tree(FileSystem::ROOT), fs.tree(FileSystem::ROOT));
+        assert_eq!(
+            parse_dir_sizes(&replayed).unwrap(),
+            parse_dir_sizes(input).unwrap()
+        );
+        assert_eq!(transcript(sandbox.root()).unwrap(), replayed);
+        replayed
+    }
+
+    #[test]
+    fn test_sandbox_round_trip() {
+        let replayed = round_trip(INPUT);
+        assert_eq!(part1(&replayed).unwrap(), "95437");
+        assert_eq!(part2_stream(&mut replayed.as_bytes()).unwrap(), "24933642");
+
+        let fs: FileSystem = INPUT.parse().unwrap();
+        let sandbox = Sandbox::new(&fs).unwrap();
+        let root = sandbox.root().to_path_buf();
+        assert_eq!(
+            std::fs::metadata(root.join("d/d.log")).unwrap().len(),
+            8033020
+        );
+        drop(sandbox);
+        assert!(!root.exists());
+    }
+
+    #[test]
+    fn test_sandbox_deep_and_odd() {
+        // a directory chain 100 deep, with a file at every level
+        let mut input = "$ cd /\n".to_string();
+        for depth in 0..100 {
+            write!(
+                input,
+                "$ ls\n{depth} file {depth}\ndir level {depth}\n$ cd level {depth}\n"
+            )
+            .unwrap();
+        }
+        input += "$ ls\n4294967296 huge";
+        let replayed = round_trip(&input);
+        // the same, plus the way back out
+        assert_eq!(replayed.lines().count(), input.lines().count() + 100);
+
+        let odd = "$ ls\ndir  leading space\ndir $ ls\ndir dir\n12 résumé.pdf\n3 trailing \n\
+                   $ cd  leading space\n$ ls\n7 ..x\n$ cd ..\n$ cd $ ls\n$ ls\n1 $ cd ..\n$ cd ..\n\
+                   $ cd dir\n$ ls\ndir dir\n$ cd dir\n$ ls\n5 -rf";
+        round_trip(odd);
+        let fs: FileSystem = odd.parse().unwrap();
+        assert_eq!(fs.size(fs.lookup("/$ ls").unwrap()), 1);
+        assert_eq!(fs.size(fs.lookup("/dir/dir/-rf").unwrap()), 5);
+    }
+
+    #[test]
+    fn test_materialize_stays_inside() {
+        let sandbox = Sandbox::new(&"$ ls\n5 f".parse().unwrap()).unwrap();
+        for name in ["..", ".", "/tmp/escaped", "a/../.."] {
+            let mut fs = FileSystem::new();
+            fs.record(
+                FileSystem::ROOT,
+                BTreeMap::from([(name.to_string(), Entry::File(1))]),
+            )
+            .unwrap();
+            assert!(materialize(&fs, sandbox.root())
+                .unwrap_err()
+                .to_string()
+                .ends_with(&format!("can't create '{name}'")));
+        }
+        assert!(!Path::new("/tmp/escaped").exists());
+
+        let bigger: FileSystem = "$ ls\n9 f".parse().unwrap();
+        assert!(materialize(&bigger, sandbox.root()).is_err());
+        assert_eq!(
+            std::fs::metadata(sandbox.root().join("f")).unwrap().len(),
+            5
+        );
+    }
+
+    #[test]
+    fn test_transcript_errors() {
+        let fs: FileSystem = "$ ls\ndir a".parse().unwrap();
+        let sandbox = Sandbox::new(&fs).unwrap();
+        File::create(sandbox.root().join("a/two\nlines")).unwrap();
+        assert!(transcript(sandbox.root())
+            .unwrap_err()
+            .to_string()
+            .ends_with("name can't be written in a transcript"));
+    }
 }
@@ -1,4 +1,6 @@
-use std::{cmp::Ordering, collections::HashSet, error::Error, str::FromStr};
+use std::{cmp::Ordering, collections::HashSet, error::Error, io::BufRead, str::FromStr};
+
+use crate::record::{self, Frame};
 
 #[derive(Debug, Default, PartialEq, Eq, Clone, Copy, Hash)]
 struct Point {
@@ -50,6 +52,17 @@ fn parse_input<'a>(
     Box::new(input.lines().flat_map(|line| iter_line(parse_line(line))))
 }
 
+fn parse_stream<'a>(
+    input: &'a mut dyn BufRead,
+) -> impl Iterator<Item = Result<Direction, Box<dyn Error>>> + 'a {
+    input.lines().flat_map(|line| {
+        iter_line(
+            line.map_err(|e| e.into())
+                .and_then(|line| parse_line(&line)),
+        )
+    })
+}
+
 impl Point {
     fn new(x: i32, y: i32) -> Self {
         Self { x, y }
@@ -95,29 +108,71 @@ fn follow(tail: i32, head: i32) -> i32 {
     }
 }
 
-fn traverse(input: &str, rope_length: usize) -> Result<HashSet<Point>, Box<dyn Error>> {
+// Only the visited squares are kept, so memory grows with the area the rope
+// covers rather than with the length of the input.
+fn traverse(
+    commands: impl Iterator<Item = Result<Direction, Box<dyn Error>>>,
+    rope_length: usize,
+) -> Result<HashSet<Point>, Box<dyn Error>> {
     let mut rope = vec![Point::default(); rope_length];
     let mut seen = HashSet::new();
     seen.insert(rope[0]);
 
-    for cmd in parse_input(input) {
+    for cmd in commands {
         rope[0] = rope[0].move_by(cmd?);
         for i in 1..rope.len() {
             rope[i] = rope[i].follow(rope[i - 1]);
         }
 
         seen.insert(*rope.last().unwrap());
+        record::frame(|| Frame::from_text(&render(&rope, &seen), None));
     }
 
     Ok(seen)
 }
 
+// Draws the rope over the visited squares, in the style of the puzzle text:
+// H for the head, knot numbers (or T for a two-knot rope), s for the start.
+fn render(rope: &[Point], seen: &HashSet<Point>) -> String {
+    let all: Vec<Point> = rope.iter().chain(seen).cloned().collect();
+    let x_min = all.iter().map(|p| p.x).min().unwrap().min(0);
+    let x_max = all.iter().map(|p| p.x).max().unwrap().max(0);
+    let y_min = all.iter().map(|p| p.y).min().unwrap().min(0);
+    let y_max = all.iter().map(|p| p.y).max().unwrap().max(0);
+
+    let mut out = String::new();
+    for y in (y_min..=y_max).rev() {
+        for x in x_min..=x_max {
+            let pt = Point::new(x, y);
+            out.push(match rope.iter().position(|k| *k == pt) {
+                Some(0) => 'H',
+                Some(_) if rope.len() == 2 => 'T',
+                // knots past `z` (35) have no digit of their own
+                Some(i) => char::from_digit(i as u32, 36).unwrap_or('*'),
+                None if pt == Point::default() => 's',
+                None if seen.contains(&pt) => '#',
+                None => '.',
+            });
+        }
+        out.push('\n');
+    }
+    out
+}
+
 pub fn part1(input: &str) -> Result<String, Box<dyn Error>> {
-    traverse(input, 2).map(|seen| seen.len().to_string())
+    traverse(parse_input(input), 2).map(|seen| seen.len().to_string())
 }
 
 pub fn part2(input: &str) -> Result<String, Box<dyn Error>> {
-    traverse(input, 10).map(|seen| seen.len().to_string())
+    traverse(parse_input(input), 10).map(|seen| seen.len().to_string())
+}
+
+pub fn part1_stream(input: &mut dyn BufRead) -> Result<String, Box<dyn Error>> {
+    traverse(parse_stream(input), 2).map(|seen| seen.len().to_string())
+}
+
+pub fn part2_stream(input: &mut dyn BufRead) -> Result<String, Box<dyn Error>> {
+    traverse(parse_stream(input), 10).map(|seen| seen.len().to_string())
 }
 
 #[cfg(test)]
@@ -136,4 +191,17 @@ mod test {
     fn test_part2() {
         assert_eq!(part2(INPUT2).unwrap(), "36")
     }
+
+    #[test]
+    fn test_stream() {
+        assert_eq!(part1_stream(&mut INPUT1.as_bytes()).unwrap(), "13");
+        assert_eq!(part2_stream(&mut INPUT2.as_bytes()).unwrap(), "36");
+    }
+
+    #[test]
+    fn test_render_long_rope() {
+        let rope: Vec<Point> = (0..40).map(|x| Point::new(x, 0)).collect();
+        let row = render(&rope, &HashSet::new());
+        assert_eq!(row, "H123456789abcdefghijklmnopqrstuvwxyz****\n");
+    }
 }
@@ -1,14 +1,45 @@
+//! Two-dimensional grids addressed by [`Location`].
+//!
+//! Every type in this module shares one axis convention: `row` 0 is the top of
+//! the grid and rows grow downwards, `col` 0 is the left edge and columns grow
+//! to the right. "Up" therefore always means a smaller `row`, both for
+//! [`Grid::up`] and for [`Location::up`], [`Direction4::Up`] and the vectors
+//! they produce.
+
 use std::{
+    collections::{BTreeMap, HashMap, HashSet},
     error::Error,
     fmt::{Display, Write},
+    ops::{Add, Mul, Neg, Sub},
     str::FromStr,
 };
 
-#[derive(Debug)]
+use crate::region;
+
+#[derive(Debug, Clone)]
 pub struct Grid<T> {
     width: usize,
     height: usize,
     rows: Vec<Vec<T>>,
+    journal: Option<Journal<T>>,
+}
+
+/// A record of every [`Grid::set`] made while journaling is on, so the grid can
+/// be stepped backwards and forwards. `position` is the number of changes
+/// currently applied; anything after it has been undone and can be redone.
+#[derive(Debug, Clone)]
+struct Journal<T> {
+    changes: Vec<CellChange<T>>,
+    position: usize,
+    checkpoints: HashMap<String, usize>,
+}
+
+/// One cell's value before and after a change, or between two checkpoints.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CellChange<T> {
+    pub loc: Location,
+    pub before: T,
+    pub after: T,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
@@ -17,9 +48,45 @@ pub struct Location {
     pub col: usize,
 }
 
+/// The locations on a line between two endpoints, inclusive, walked from the
+/// start towards the end. Lines that are not horizontal, vertical or at 45
+/// degrees are rasterised Bresenham-style, so every step advances one cell
+/// along the longer axis.
+#[derive(Debug, Clone)]
 pub struct LocationRange {
     start: Location,
-    end: Location,
+    delta: Vector,
+    front: usize,
+    back: usize,
+}
+
+/// A signed offset between two [`Location`]s.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Vector {
+    pub row: isize,
+    pub col: isize,
+}
+
+/// The four orthogonal directions, listed clockwise from `Up`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Direction4 {
+    Up,
+    Right,
+    Down,
+    Left,
+}
+
+/// The four orthogonal and four diagonal directions, listed clockwise from `Up`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Direction8 {
+    Up,
+    UpRight,
+    Right,
+    DownRight,
+    Down,
+    DownLeft,
+    Left,
+    UpLeft,
 }
 
 impl<T: PartialEq + Default + Clone> Grid<T> {
@@ -28,6 +95,7 @@ impl<T: PartialEq + Default + Clone> Grid<T> {
             width,
             height,
             rows: vec![vec![T::default(); width]; height],
+            journal: None,
         }
     }
 
@@ -37,6 +105,7 @@ impl<T: PartialEq + Default + Clone> Grid<T> {
             width: v[0].len(),
             height: v.len(),
             rows: v,
+            journal: None,
         }
     }
 
@@ -54,7 +123,17 @@ impl<T: PartialEq + Default + Clone> Grid<T> {
     }
 
     pub fn set(&mut self, loc: Location, item: T) {
-        self.rows[loc.row][loc.col] = item
+        match &mut self.journal {
+            Some(journal) => {
+                let before = std::mem::replace(&mut self.rows[loc.row][loc.col], item.clone());
+                journal.record(CellChange {
+                    loc,
+                    before,
+                    after: item,
+                });
+            }
+            None => self.rows[loc.row][loc.col] = item,
+        }
     }
 
     pub fn locations<'a>(&'a self) -> Box<dyn Iterator<Item = Location> + 'a> {
@@ -77,22 +156,31 @@ impl<T: PartialEq + Default + Clone> Grid<T> {
 
     #[allow(dead_code)]
     pub fn up(&self, loc: Location) -> Option<Location> {
-        (loc.row > 0).then(|| Location::new(loc.row - 1, loc.col))
+        self.offset(loc, Direction4::Up.vector())
     }
 
     #[allow(dead_code)]
     pub fn down(&self, loc: Location) -> Option<Location> {
-        (loc.row < self.height - 1).then(|| Location::new(loc.row + 1, loc.col))
+        self.offset(loc, Direction4::Down.vector())
     }
 
     #[allow(dead_code)]
     pub fn left(&self, loc: Location) -> Option<Location> {
-        (loc.col > 0).then(|| Location::new(loc.row, loc.col - 1))
+        self.offset(loc, Direction4::Left.vector())
     }
 
     #[allow(dead_code)]
     pub fn right(&self, loc: Location) -> Option<Location> {
-        (loc.col < self.width - 1).then(|| Location::new(loc.row, loc.col + 1))
+        self.offset(loc, Direction4::Right.vector())
+    }
+
+    /// Moves `loc` by `v`, returning `None` if the result is outside the grid.
+    pub fn offset(&self, loc: Location, v: Vector) -> Option<Location> {
+        (loc + v).filter(|l| self.contains(*l))
+    }
+
+    pub fn contains(&self, loc: Location) -> bool {
+        loc.row < self.height && loc.col < self.width
     }
 
     #[allow(dead_code)]
@@ -100,11 +188,201 @@ impl<T: PartialEq + Default + Clone> Grid<T> {
         &self.rows
     }
 
+    /// Collects the cells orthogonally reachable from `seed` through cells
+    /// whose values are `passable`.
+    #[allow(dead_code)]
+    pub fn flood_fill(&self, seed: Location, passable: impl Fn(&T) -> bool) -> HashSet<Location> {
+        region::flood_fill(
+            seed,
+            |l| self.neighbors(l),
+            |l| self.get(l).is_some_and(&passable),
+        )
+    }
+
+    /// Labels every cell with the index of its region, where orthogonal
+    /// neighbours share a region if `connected` holds for their values. Also
+    /// returns the size of each region.
+    #[allow(dead_code)]
+    pub fn label_components(
+        &self,
+        connected: impl Fn(&T, &T) -> bool,
+    ) -> (Grid<usize>, Vec<usize>) {
+        let components = region::label_components(
+            self.locations(),
+            |l| self.neighbors(l),
+            |a, b| connected(&self.rows[a.row][a.col], &self.rows[b.row][b.col]),
+        );
+
+        let mut labels = Grid::new(self.width, self.height);
+        for (loc, label) in components.labels {
+            labels.set(loc, label);
+        }
+        (labels, components.sizes)
+    }
+
+    /// The cells of `region` that touch a cell outside it or the edge of the
+    /// grid.
+    #[allow(dead_code)]
+    pub fn region_boundary(&self, region: &HashSet<Location>) -> Vec<Location> {
+        let mut cells: Vec<Location> = region
+            .iter()
+            .filter(|l| self.open_sides(**l, region) > 0)
+            .cloned()
+            .collect();
+        cells.sort();
+        cells
+    }
+
+    /// The number of cell sides between `region` and the rest of the grid,
+    /// counting sides on the edge of the grid.
+    #[allow(dead_code)]
+    pub fn perimeter(&self, region: &HashSet<Location>) -> usize {
+        region.iter().map(|l| self.open_sides(*l, region)).sum()
+    }
+
+    fn open_sides(&self, loc: Location, region: &HashSet<Location>) -> usize {
+        Direction4::ALL
+            .iter()
+            .filter(|d| {
+                self.offset(loc, d.vector())
+                    .is_none_or(|n| !region.contains(&n))
+            })
+            .count()
+    }
+
+    /// Starts recording every `set` so it can be undone. Resizing the grid
+    /// can't be undone, so it forgets all history and checkpoints.
+    #[allow(dead_code)]
+    pub fn enable_journal(&mut self) {
+        self.journal.get_or_insert_with(|| Journal {
+            changes: Vec::new(),
+            position: 0,
+            checkpoints: HashMap::new(),
+        });
+    }
+
+    /// Stops recording and forgets all history and checkpoints.
+    #[allow(dead_code)]
+    pub fn disable_journal(&mut self) {
+        self.journal = None;
+    }
+
+    /// Reverts up to `n` of the most recent changes, returning how many were
+    /// reverted.
+    #[allow(dead_code)]
+    pub fn undo(&mut self, n: usize) -> usize {
+        let Some(journal) = &mut self.journal else {
+            return 0;
+        };
+
+        let count = n.min(journal.position);
+        for _ in 0..count {
+            journal.position -= 1;
+            let change = &journal.changes[journal.position];
+            self.rows[change.loc.row][change.loc.col] = change.before.clone();
+        }
+        count
+    }
+
+    /// Reapplies up to `n` undone changes, returning how many were reapplied.
+    #[allow(dead_code)]
+    pub fn redo(&mut self, n: usize) -> usize {
+        let Some(journal) = &mut self.journal else {
+            return 0;
+        };
+
+        let count = n.min(journal.changes.len() - journal.position);
+        for _ in 0..count {
+            let change = &journal.changes[journal.position];
+            self.rows[change.loc.row][change.loc.col] = change.after.clone();
+            journal.position += 1;
+        }
+        count
+    }
+
+    /// Names the current state so it can be restored or diffed later.
+    /// Reusing a name moves the checkpoint.
+    #[allow(dead_code)]
+    pub fn checkpoint(&mut self, name: &str) -> Result<(), Box<dyn Error>> {
+        let journal = self.journal.as_mut().ok_or("journaling is not enabled")?;
+        journal
+            .checkpoints
+            .insert(name.to_string(), journal.position);
+        Ok(())
+    }
+
+    /// Undoes or redoes changes until the grid is back at checkpoint `name`.
+    #[allow(dead_code)]
+    pub fn restore(&mut self, name: &str) -> Result<(), Box<dyn Error>> {
+        let target = self.checkpoint_position(name)?;
+        let position = self.journal.as_ref().map_or(0, |j| j.position);
+        if target < position {
+            self.undo(position - target);
+        } else {
+            self.redo(target - position);
+        }
+        Ok(())
+    }
+
+    /// Lists the cells whose value differs between checkpoints `from` and
+    /// `to`, in location order. Cells that changed and then changed back are
+    /// left out.
+    #[allow(dead_code)]
+    pub fn diff(&self, from: &str, to: &str) -> Result<Vec<CellChange<T>>, Box<dyn Error>> {
+        let (from, to) = (
+            self.checkpoint_position(from)?,
+            self.checkpoint_position(to)?,
+        );
+        let changes = &self.journal.as_ref().unwrap().changes;
+
+        // Walk the changes between the two checkpoints in the direction of
+        // travel, keeping each cell's first "before" and last "after".
+        let mut cells: BTreeMap<Location, (T, T)> = BTreeMap::new();
+        let mut track = |loc: Location, before: &T, after: &T| {
+            cells
+                .entry(loc)
+                .and_modify(|(_, last)| *last = after.clone())
+                .or_insert_with(|| (before.clone(), after.clone()));
+        };
+        if from <= to {
+            for c in &changes[from..to] {
+                track(c.loc, &c.before, &c.after);
+            }
+        } else {
+            for c in changes[to..from].iter().rev() {
+                track(c.loc, &c.after, &c.before);
+            }
+        }
+
+        Ok(cells
+            .into_iter()
+            .filter(|(_, (before, after))| before != after)
+            .map(|(loc, (before, after))| CellChange { loc, before, after })
+            .collect())
+    }
+
+    fn checkpoint_position(&self, name: &str) -> Result<usize, Box<dyn Error>> {
+        let journal = self.journal.as_ref().ok_or("journaling is not enabled")?;
+        journal
+            .checkpoints
+            .get(name)
+            .copied()
+            .ok_or_else(|| format!("unknown checkpoint '{name}'").into())
+    }
+
     pub fn expand(&mut self, rows: usize, columns: usize) {
+        // recorded changes may be to cells that are about to go
+        if let Some(journal) = &mut self.journal {
+            journal.changes.clear();
+            journal.position = 0;
+            journal.checkpoints.clear();
+        }
         for row in &mut self.rows {
             row.resize(columns, T::default())
         }
         self.rows.resize(rows, vec![T::default(); columns]);
+        self.width = columns;
+        self.height = rows;
     }
 }
 
@@ -121,58 +399,302 @@ impl<T: Display> Display for Grid<T> {
     }
 }
 
+impl<T> Journal<T> {
+    /// Appends a change, discarding anything that had been undone along with
+    /// any checkpoints that pointed into it.
+    fn record(&mut self, change: CellChange<T>) {
+        self.changes.truncate(self.position);
+        let position = self.position;
+        self.checkpoints.retain(|_, p| *p <= position);
+        self.changes.push(change);
+        self.position += 1;
+    }
+}
+
 impl Location {
     pub fn new(row: usize, col: usize) -> Self {
         Self { row, col }
     }
 
+    #[allow(dead_code)]
     pub fn up(&self) -> Self {
-        Self::new(self.row + 1, self.col)
+        Self::new(self.row - 1, self.col)
     }
 
     #[allow(dead_code)]
     pub fn down(&self) -> Self {
-        Self::new(self.row - 1, self.col)
+        Self::new(self.row + 1, self.col)
     }
 
+    #[allow(dead_code)]
     pub fn right(&self) -> Self {
         Self::new(self.row, self.col + 1)
     }
 
+    #[allow(dead_code)]
     pub fn left(&self) -> Self {
         Self::new(self.row, self.col - 1)
     }
 
+    /// Moves this location by `v`, returning `None` if either coordinate
+    /// would become negative.
+    pub fn checked_add(&self, v: Vector) -> Option<Self> {
+        Some(Self::new(
+            self.row.checked_add_signed(v.row)?,
+            self.col.checked_add_signed(v.col)?,
+        ))
+    }
+
+    #[allow(dead_code)]
+    pub fn step(&self, d: Direction4) -> Option<Self> {
+        self.checked_add(d.vector())
+    }
+
+    /// Returns the straight line from this location to `other`, or `None` if
+    /// the two are not on the same row, column or 45 degree diagonal.
     pub fn to(&self, other: &Location) -> Option<LocationRange> {
-        if self.row == other.row || self.col == other.col {
-            Some(LocationRange {
-                start: *self.min(other),
-                end: *self.max(other),
-            })
+        let delta = *other - *self;
+        if delta.row == 0 || delta.col == 0 || delta.row.abs() == delta.col.abs() {
+            Some(LocationRange::new(*self, *other))
         } else {
             None
         }
     }
+
+    /// Returns the rasterised line from this location to `other`, at any slope.
+    #[allow(dead_code)]
+    pub fn line_to(&self, other: &Location) -> LocationRange {
+        LocationRange::new(*self, *other)
+    }
+}
+
+impl Add<Vector> for Location {
+    type Output = Option<Location>;
+
+    fn add(self, v: Vector) -> Self::Output {
+        self.checked_add(v)
+    }
+}
+
+impl Sub<Location> for Location {
+    type Output = Vector;
+
+    fn sub(self, other: Location) -> Self::Output {
+        Vector::new(
+            self.row as isize - other.row as isize,
+            self.col as isize - other.col as isize,
+        )
+    }
+}
+
+impl Vector {
+    pub const fn new(row: isize, col: isize) -> Self {
+        Self { row, col }
+    }
+
+    /// The larger of the two components' magnitudes, i.e. the number of king
+    /// moves needed to cover this offset.
+    #[allow(dead_code)]
+    pub fn chebyshev(&self) -> usize {
+        self.row.unsigned_abs().max(self.col.unsigned_abs())
+    }
+
+    #[allow(dead_code)]
+    pub fn manhattan(&self) -> usize {
+        self.row.unsigned_abs() + self.col.unsigned_abs()
+    }
+
+    /// Reduces each component to -1, 0 or 1.
+    #[allow(dead_code)]
+    pub fn signum(&self) -> Self {
+        Self::new(self.row.signum(), self.col.signum())
+    }
+}
+
+impl Add for Vector {
+    type Output = Vector;
+
+    fn add(self, other: Vector) -> Self::Output {
+        Vector::new(self.row + other.row, self.col + other.col)
+    }
+}
+
+impl Sub for Vector {
+    type Output = Vector;
+
+    fn sub(self, other: Vector) -> Self::Output {
+        Vector::new(self.row - other.row, self.col - other.col)
+    }
+}
+
+impl Mul<isize> for Vector {
+    type Output = Vector;
+
+    fn mul(self, n: isize) -> Self::Output {
+        Vector::new(self.row * n, self.col * n)
+    }
+}
+
+impl Neg for Vector {
+    type Output = Vector;
+
+    fn neg(self) -> Self::Output {
+        Vector::new(-self.row, -self.col)
+    }
+}
+
+impl Direction4 {
+    #[allow(dead_code)]
+    pub const ALL: [Direction4; 4] = [Self::Up, Self::Right, Self::Down, Self::Left];
+
+    pub const fn vector(self) -> Vector {
+        match self {
+            Self::Up => Vector::new(-1, 0),
+            Self::Right => Vector::new(0, 1),
+            Self::Down => Vector::new(1, 0),
+            Self::Left => Vector::new(0, -1),
+        }
+    }
+
+    #[allow(dead_code)]
+    pub fn turn_left(self) -> Self {
+        match self {
+            Self::Up => Self::Left,
+            Self::Right => Self::Up,
+            Self::Down => Self::Right,
+            Self::Left => Self::Down,
+        }
+    }
+
+    #[allow(dead_code)]
+    pub fn turn_right(self) -> Self {
+        match self {
+            Self::Up => Self::Right,
+            Self::Right => Self::Down,
+            Self::Down => Self::Left,
+            Self::Left => Self::Up,
+        }
+    }
+
+    #[allow(dead_code)]
+    pub fn opposite(self) -> Self {
+        self.turn_right().turn_right()
+    }
+}
+
+impl Direction8 {
+    #[allow(dead_code)]
+    pub const ALL: [Direction8; 8] = [
+        Self::Up,
+        Self::UpRight,
+        Self::Right,
+        Self::DownRight,
+        Self::Down,
+        Self::DownLeft,
+        Self::Left,
+        Self::UpLeft,
+    ];
+
+    pub const fn vector(self) -> Vector {
+        match self {
+            Self::Up => Vector::new(-1, 0),
+            Self::UpRight => Vector::new(-1, 1),
+            Self::Right => Vector::new(0, 1),
+            Self::DownRight => Vector::new(1, 1),
+            Self::Down => Vector::new(1, 0),
+            Self::DownLeft => Vector::new(1, -1),
+            Self::Left => Vector::new(0, -1),
+            Self::UpLeft => Vector::new(-1, -1),
+        }
+    }
+
+    /// Rotates 45 degrees anticlockwise.
+    #[allow(dead_code)]
+    pub fn turn_left(self) -> Self {
+        Self::ALL[(self as usize + 7) % 8]
+    }
+
+    /// Rotates 45 degrees clockwise.
+    #[allow(dead_code)]
+    pub fn turn_right(self) -> Self {
+        Self::ALL[(self as usize + 1) % 8]
+    }
+
+    #[allow(dead_code)]
+    pub fn opposite(self) -> Self {
+        Self::ALL[(self as usize + 4) % 8]
+    }
+}
+
+impl From<Direction4> for Direction8 {
+    fn from(d: Direction4) -> Self {
+        match d {
+            Direction4::Up => Self::Up,
+            Direction4::Right => Self::Right,
+            Direction4::Down => Self::Down,
+            Direction4::Left => Self::Left,
+        }
+    }
+}
+
+impl LocationRange {
+    fn new(start: Location, end: Location) -> Self {
+        let delta = end - start;
+        Self {
+            start,
+            delta,
+            front: 0,
+            back: delta.chebyshev() + 1,
+        }
+    }
+
+    /// The location `i` steps along the line. Each minor-axis coordinate is
+    /// rounded to the nearest cell, with ties rounded away from the start.
+    fn nth_location(&self, i: usize) -> Location {
+        let steps = self.delta.chebyshev() as isize;
+        if steps == 0 {
+            return self.start;
+        }
+
+        let offset = |d: isize| {
+            let n = d * i as isize;
+            (2 * n + steps * n.signum()) / (2 * steps)
+        };
+
+        Location::new(
+            self.start.row.wrapping_add_signed(offset(self.delta.row)),
+            self.start.col.wrapping_add_signed(offset(self.delta.col)),
+        )
+    }
 }
 
 impl Iterator for LocationRange {
     type Item = Location;
 
     fn next(&mut self) -> Option<Self::Item> {
-        if self.start <= self.end {
-            let current = self.start;
-            if self.start.row == self.end.row {
-                self.start.col += 1;
-            } else {
-                self.start.row += 1;
-            }
-            Some(current)
-        } else {
-            None
-        }
+        (self.front < self.back).then(|| {
+            self.front += 1;
+            self.nth_location(self.front - 1)
+        })
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = self.back - self.front;
+        (len, Some(len))
     }
 }
 
+impl DoubleEndedIterator for LocationRange {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        (self.front < self.back).then(|| {
+            self.back -= 1;
+            self.nth_location(self.back)
+        })
+    }
+}
+
+impl ExactSizeIterator for LocationRange {}
+
 impl FromStr for Location {
     type Err = Box<dyn Error>;
 
@@ -183,3 +705,171 @@ impl FromStr for Location {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_location_directions_match_grid() {
+        let grid: Grid<u8> = Grid::new(3, 3);
+        let center = Location::new(1, 1);
+        assert_eq!(grid.up(center), Some(center.up()));
+        assert_eq!(grid.down(center), Some(center.down()));
+        assert_eq!(grid.left(center), Some(center.left()));
+        assert_eq!(grid.right(center), Some(center.right()));
+    }
+
+    #[test]
+    fn test_checked_add() {
+        let grid: Grid<u8> = Grid::new(3, 3);
+        let origin = Location::new(0, 0);
+        assert_eq!(origin + Direction4::Up.vector(), None);
+        assert_eq!(
+            origin + Direction8::DownRight.vector() * 2,
+            Some(Location::new(2, 2))
+        );
+        assert_eq!(grid.offset(origin, Vector::new(0, 3)), None);
+        assert_eq!(
+            Location::new(2, 0) - Location::new(0, 1),
+            Vector::new(2, -1)
+        );
+    }
+
+    #[test]
+    fn test_turns() {
+        for d in Direction4::ALL {
+            assert_eq!(d.turn_left().turn_right(), d);
+            assert_eq!(d.opposite().vector(), -d.vector());
+        }
+        for d in Direction8::ALL {
+            assert_eq!(
+                d.turn_right().turn_right(),
+                d.opposite().turn_left().turn_left()
+            );
+            assert_eq!(d.opposite().vector(), -d.vector());
+        }
+        assert_eq!(Direction8::Up.turn_left(), Direction8::UpLeft);
+    }
+
+    #[test]
+    fn test_regions() {
+        let grid = Grid::from_vec(vec![vec![1, 1, 2], vec![2, 1, 2], vec![1, 2, 2]]);
+
+        let ones = grid.flood_fill(Location::new(0, 0), |v| *v == 1);
+        assert_eq!(ones.len(), 3);
+        assert_eq!(grid.perimeter(&ones), 8);
+        assert_eq!(grid.region_boundary(&ones).len(), 3);
+
+        let (labels, sizes) = grid.label_components(|a, b| a == b);
+        assert_eq!(sizes, vec![3, 4, 1, 1]);
+        assert_eq!(labels.get(Location::new(2, 2)), Some(&1));
+        assert_eq!(labels.get(Location::new(2, 0)), Some(&3));
+    }
+
+    #[test]
+    fn test_journal_undo_redo() {
+        let mut grid: Grid<char> = Grid::from_vec(vec![vec!['.'; 3]; 2]);
+        grid.set(Location::new(0, 0), 'a');
+        assert_eq!(grid.undo(1), 0);
+
+        grid.enable_journal();
+        grid.set(Location::new(0, 1), 'b');
+        grid.set(Location::new(1, 2), 'c');
+        assert_eq!(grid.to_string(), "ab.\n..c\n");
+
+        assert_eq!(grid.undo(5), 2);
+        assert_eq!(grid.to_string(), "a..\n...\n");
+        assert_eq!(grid.redo(1), 1);
+        assert_eq!(grid.to_string(), "ab.\n...\n");
+
+        // a new change discards the undone one
+        grid.set(Location::new(1, 0), 'd');
+        assert_eq!(grid.redo(1), 0);
+        assert_eq!(grid.to_string(), "ab.\nd..\n");
+    }
+
+    #[test]
+    fn test_journal_checkpoints() {
+        let mut grid: Grid<u8> = Grid::new(2, 2);
+        grid.enable_journal();
+        grid.checkpoint("start").unwrap();
+        grid.set(Location::new(0, 0), 1);
+        grid.set(Location::new(1, 1), 2);
+        grid.set(Location::new(0, 0), 3);
+        grid.set(Location::new(1, 1), 0);
+        grid.checkpoint("end").unwrap();
+
+        let expected = vec![CellChange {
+            loc: Location::new(0, 0),
+            before: 0,
+            after: 3,
+        }];
+        assert_eq!(grid.diff("start", "end").unwrap(), expected);
+        assert_eq!(grid.diff("end", "start").unwrap()[0].after, 0);
+        assert!(grid.diff("start", "middle").is_err());
+
+        grid.restore("start").unwrap();
+        assert_eq!(grid.get(Location::new(0, 0)), Some(&0));
+        grid.restore("end").unwrap();
+        assert_eq!(grid.get(Location::new(0, 0)), Some(&3));
+
+        grid.undo(1);
+        grid.set(Location::new(0, 1), 4);
+        assert!(grid.restore("end").is_err());
+    }
+
+    #[test]
+    fn test_journal_forgets_resize() {
+        let mut grid: Grid<u8> = Grid::new(3, 3);
+        grid.enable_journal();
+        grid.checkpoint("start").unwrap();
+        grid.set(Location::new(2, 2), 1);
+        grid.expand(2, 2);
+        assert_eq!(grid.undo(1), 0);
+        assert!(grid.restore("start").is_err());
+
+        grid.set(Location::new(1, 1), 2);
+        assert_eq!(grid.undo(1), 1);
+        assert_eq!(grid.get(Location::new(1, 1)), Some(&0));
+    }
+
+    #[test]
+    fn test_range_keeps_direction() {
+        let range = Location::new(2, 4).to(&Location::new(2, 1)).unwrap();
+        assert_eq!(range.len(), 4);
+        assert_eq!(
+            range.collect::<Vec<_>>(),
+            [(2, 4), (2, 3), (2, 2), (2, 1)].map(|(r, c)| Location::new(r, c))
+        );
+
+        let mut range = Location::new(0, 0).to(&Location::new(0, 0)).unwrap();
+        assert_eq!(range.next(), Some(Location::new(0, 0)));
+        assert_eq!(range.next(), None);
+    }
+
+    #[test]
+    fn test_range_diagonal_and_reverse() {
+        let range = Location::new(3, 0).to(&Location::new(0, 3)).unwrap();
+        assert_eq!(
+            range.rev().collect::<Vec<_>>(),
+            [(0, 3), (1, 2), (2, 1), (3, 0)].map(|(r, c)| Location::new(r, c))
+        );
+        assert!(Location::new(0, 0).to(&Location::new(1, 2)).is_none());
+    }
+
+    #[test]
+    fn test_line_to() {
+        let line = Location::new(0, 0).line_to(&Location::new(2, 5));
+        assert_eq!(line.len(), 6);
+        assert_eq!(
+            line.collect::<Vec<_>>(),
+            [(0, 0), (0, 1), (1, 2), (1, 3), (2, 4), (2, 5)].map(|(r, c)| Location::new(r, c))
+        );
+
+        let mut line = Location::new(2, 5).line_to(&Location::new(0, 0));
+        assert_eq!(line.next(), Some(Location::new(2, 5)));
+        assert_eq!(line.next_back(), Some(Location::new(0, 0)));
+        assert_eq!(line.len(), 4);
+    }
+}
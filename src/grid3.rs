@@ -0,0 +1,255 @@
+//! Three-dimensional points, bounding boxes and dense voxel grids.
+
+use std::{
+    error::Error,
+    fmt::Display,
+    ops::{Add, Mul, Neg, Sub},
+    str::FromStr,
+};
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Point3 {
+    pub x: i64,
+    pub y: i64,
+    pub z: i64,
+}
+
+/// An axis-aligned box of points, inclusive at both corners.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Bounds3 {
+    pub min: Point3,
+    pub max: Point3,
+}
+
+/// A dense grid holding one value for every point in a [`Bounds3`].
+#[derive(Debug, Clone)]
+pub struct VoxelGrid<T> {
+    bounds: Bounds3,
+    cells: Vec<T>,
+}
+
+impl Point3 {
+    pub const fn new(x: i64, y: i64, z: i64) -> Self {
+        Self { x, y, z }
+    }
+
+    /// The points sharing a face with this one.
+    pub fn neighbors6(&self) -> [Point3; 6] {
+        [
+            Point3::new(1, 0, 0),
+            Point3::new(-1, 0, 0),
+            Point3::new(0, 1, 0),
+            Point3::new(0, -1, 0),
+            Point3::new(0, 0, 1),
+            Point3::new(0, 0, -1),
+        ]
+        .map(|d| *self + d)
+    }
+
+    /// The points sharing a face or an edge with this one.
+    #[allow(dead_code)]
+    pub fn neighbors18(&self) -> Vec<Point3> {
+        self.offsets(2)
+    }
+
+    /// The points sharing a face, an edge or a corner with this one.
+    #[allow(dead_code)]
+    pub fn neighbors26(&self) -> Vec<Point3> {
+        self.offsets(3)
+    }
+
+    /// Every point one step away along each axis, changing at most
+    /// `max_axes` coordinates at once.
+    fn offsets(&self, max_axes: usize) -> Vec<Point3> {
+        let mut points = Vec::new();
+        for x in -1..=1 {
+            for y in -1..=1 {
+                for z in -1..=1 {
+                    let changed = [x, y, z].iter().filter(|c| **c != 0).count();
+                    if changed > 0 && changed <= max_axes {
+                        points.push(*self + Point3::new(x, y, z));
+                    }
+                }
+            }
+        }
+        points
+    }
+
+    #[allow(dead_code)]
+    pub fn manhattan(&self, other: Point3) -> i64 {
+        (self.x - other.x).abs() + (self.y - other.y).abs() + (self.z - other.z).abs()
+    }
+}
+
+impl Add for Point3 {
+    type Output = Point3;
+
+    fn add(self, other: Point3) -> Self::Output {
+        Point3::new(self.x + other.x, self.y + other.y, self.z + other.z)
+    }
+}
+
+impl Sub for Point3 {
+    type Output = Point3;
+
+    fn sub(self, other: Point3) -> Self::Output {
+        Point3::new(self.x - other.x, self.y - other.y, self.z - other.z)
+    }
+}
+
+impl Mul<i64> for Point3 {
+    type Output = Point3;
+
+    fn mul(self, n: i64) -> Self::Output {
+        Point3::new(self.x * n, self.y * n, self.z * n)
+    }
+}
+
+impl Neg for Point3 {
+    type Output = Point3;
+
+    fn neg(self) -> Self::Output {
+        Point3::new(-self.x, -self.y, -self.z)
+    }
+}
+
+impl Display for Point3 {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_fmt(format_args!("({},{},{})", self.x, self.y, self.z))
+    }
+}
+
+impl FromStr for Point3 {
+    type Err = Box<dyn Error>;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let coords: Vec<&str> = s.trim().split(',').collect();
+        match coords.as_slice() {
+            [x, y, z] => Ok(Self::new(
+                x.trim().parse()?,
+                y.trim().parse()?,
+                z.trim().parse()?,
+            )),
+            _ => Err(format!("malformed point '{s}'").into()),
+        }
+    }
+}
+
+impl Bounds3 {
+    /// The smallest box containing every point, or `None` if there are none.
+    pub fn from_points<'a>(points: impl IntoIterator<Item = &'a Point3>) -> Option<Self> {
+        points.into_iter().fold(None, |bounds, p| {
+            Some(match bounds {
+                None => Bounds3 { min: *p, max: *p },
+                Some(Bounds3 { min, max }) => Bounds3 {
+                    min: Point3::new(min.x.min(p.x), min.y.min(p.y), min.z.min(p.z)),
+                    max: Point3::new(max.x.max(p.x), max.y.max(p.y), max.z.max(p.z)),
+                },
+            })
+        })
+    }
+
+    /// Grows the box by `n` in every direction.
+    pub fn expand(&self, n: i64) -> Self {
+        let pad = Point3::new(n, n, n);
+        Self {
+            min: self.min - pad,
+            max: self.max + pad,
+        }
+    }
+
+    pub fn contains(&self, p: Point3) -> bool {
+        (self.min.x..=self.max.x).contains(&p.x)
+            && (self.min.y..=self.max.y).contains(&p.y)
+            && (self.min.z..=self.max.z).contains(&p.z)
+    }
+
+    /// The number of points along each axis.
+    pub fn size(&self) -> Point3 {
+        self.max - self.min + Point3::new(1, 1, 1)
+    }
+
+    pub fn volume(&self) -> usize {
+        let size = self.size();
+        (size.x * size.y * size.z).max(0) as usize
+    }
+
+    #[allow(dead_code)]
+    pub fn points(&self) -> impl Iterator<Item = Point3> {
+        let Bounds3 { min, max } = *self;
+        (min.z..=max.z).flat_map(move |z| {
+            (min.y..=max.y).flat_map(move |y| (min.x..=max.x).map(move |x| Point3::new(x, y, z)))
+        })
+    }
+}
+
+impl<T: Default + Clone> VoxelGrid<T> {
+    pub fn new(bounds: Bounds3) -> Self {
+        Self {
+            bounds,
+            cells: vec![T::default(); bounds.volume()],
+        }
+    }
+
+    #[allow(dead_code)]
+    pub fn bounds(&self) -> Bounds3 {
+        self.bounds
+    }
+
+    pub fn get(&self, p: Point3) -> Option<&T> {
+        self.index(p).map(|i| &self.cells[i])
+    }
+
+    pub fn set(&mut self, p: Point3, value: T) {
+        let i = self
+            .index(p)
+            .unwrap_or_else(|| panic!("{p} is outside {:?}", self.bounds));
+        self.cells[i] = value;
+    }
+
+    fn index(&self, p: Point3) -> Option<usize> {
+        if !self.bounds.contains(p) {
+            return None;
+        }
+
+        let size = self.bounds.size();
+        let offset = p - self.bounds.min;
+        Some(((offset.z * size.y + offset.y) * size.x + offset.x) as usize)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        assert_eq!("1,-2,3".parse::<Point3>().unwrap(), Point3::new(1, -2, 3));
+        assert!("1,2".parse::<Point3>().is_err());
+        assert!("1,2,x".parse::<Point3>().is_err());
+    }
+
+    #[test]
+    fn test_neighborhoods() {
+        let p = Point3::new(1, 1, 1);
+        assert_eq!(p.neighbors18().len(), 18);
+        assert_eq!(p.neighbors26().len(), 26);
+        assert!(p.neighbors6().iter().all(|n| n.manhattan(p) == 1));
+        assert!(p.neighbors18().iter().all(|n| n.manhattan(p) <= 2));
+        assert!(p.neighbors26().contains(&Point3::new(0, 0, 0)));
+    }
+
+    #[test]
+    fn test_voxel_grid() {
+        let points = [Point3::new(-1, 0, 2), Point3::new(1, 3, 2)];
+        let bounds = Bounds3::from_points(&points).unwrap();
+        assert_eq!(bounds.volume(), 12);
+        assert_eq!(bounds.points().count(), 12);
+
+        let mut grid: VoxelGrid<u8> = VoxelGrid::new(bounds.expand(1));
+        grid.set(points[1], 7);
+        assert_eq!(grid.get(points[1]), Some(&7));
+        assert_eq!(grid.get(points[0]), Some(&0));
+        assert_eq!(grid.get(Point3::new(3, 0, 0)), None);
+    }
+}
@@ -0,0 +1,287 @@
+//! Sets of integers stored as sorted, disjoint, inclusive intervals.
+//!
+//! An [`IntervalSet`] keeps its intervals coalesced: overlapping or touching
+//! intervals are merged as they are inserted, so `1..=3` and `4..=6` are
+//! stored as `1..=6`. Every operation keeps that invariant, which means two
+//! sets are equal exactly when they contain the same integers.
+
+use std::ops::RangeInclusive;
+
+/// An integer type that can be used as an interval endpoint.
+pub trait Endpoint: Copy + Ord {
+    fn checked_succ(self) -> Option<Self>;
+    fn checked_pred(self) -> Option<Self>;
+    /// The number of integers in `lo..=hi`, which must not be empty.
+    fn span(lo: Self, hi: Self) -> u64;
+}
+
+macro_rules! impl_endpoint {
+    ($($t:ty),*) => {
+        $(impl Endpoint for $t {
+            fn checked_succ(self) -> Option<Self> {
+                self.checked_add(1)
+            }
+
+            fn checked_pred(self) -> Option<Self> {
+                self.checked_sub(1)
+            }
+
+            fn span(lo: Self, hi: Self) -> u64 {
+                (hi as i128 - lo as i128 + 1) as u64
+            }
+        })*
+    };
+}
+
+impl_endpoint!(i32, i64, isize, u32, u64, usize);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IntervalSet<T> {
+    // sorted, disjoint, and never touching
+    intervals: Vec<(T, T)>,
+}
+
+impl<T: Endpoint> IntervalSet<T> {
+    pub fn new() -> Self {
+        Self {
+            intervals: Vec::new(),
+        }
+    }
+
+    #[allow(dead_code)]
+    pub fn is_empty(&self) -> bool {
+        self.intervals.is_empty()
+    }
+
+    /// The intervals in ascending order.
+    pub fn iter(&self) -> impl Iterator<Item = RangeInclusive<T>> + '_ {
+        self.intervals.iter().map(|&(lo, hi)| lo..=hi)
+    }
+
+    /// How many integers the set contains.
+    pub fn len(&self) -> u64 {
+        self.intervals.iter().map(|&(lo, hi)| T::span(lo, hi)).sum()
+    }
+
+    /// The smallest and largest members, if any.
+    #[allow(dead_code)]
+    pub fn hull(&self) -> Option<RangeInclusive<T>> {
+        let first = self.intervals.first()?;
+        let last = self.intervals.last()?;
+        Some(first.0..=last.1)
+    }
+
+    #[allow(dead_code)]
+    pub fn contains(&self, value: T) -> bool {
+        self.covers(&(value..=value))
+    }
+
+    /// Whether every integer in `range` is in the set.
+    pub fn covers(&self, range: &RangeInclusive<T>) -> bool {
+        range.is_empty()
+            || self
+                .intervals
+                .iter()
+                .any(|&(lo, hi)| lo <= *range.start() && *range.end() <= hi)
+    }
+
+    /// Adds `range`, merging it with any intervals it overlaps or touches.
+    pub fn insert(&mut self, range: RangeInclusive<T>) {
+        if range.is_empty() {
+            return;
+        }
+        let (mut lo, mut hi) = range.into_inner();
+
+        // the first interval that ends at or after lo - 1
+        let first = self
+            .intervals
+            .partition_point(|&(_, end)| end.checked_succ().is_some_and(|e| e < lo));
+        // one past the last interval that starts at or before hi + 1
+        let last = self
+            .intervals
+            .partition_point(|&(start, _)| hi.checked_succ().is_none_or(|h| start <= h));
+
+        if first < last {
+            lo = lo.min(self.intervals[first].0);
+            hi = hi.max(self.intervals[last - 1].1);
+        }
+        self.intervals.splice(first..last, [(lo, hi)]);
+    }
+
+    /// Takes every integer in `range` out of the set.
+    pub fn remove(&mut self, range: RangeInclusive<T>) {
+        if range.is_empty() {
+            return;
+        }
+        let (lo, hi) = range.into_inner();
+
+        let first = self.intervals.partition_point(|&(_, end)| end < lo);
+        let last = self.intervals.partition_point(|&(start, _)| start <= hi);
+        if first >= last {
+            return;
+        }
+
+        let mut kept = Vec::new();
+        let (start, _) = self.intervals[first];
+        let (_, end) = self.intervals[last - 1];
+        if start < lo {
+            kept.push((start, lo.checked_pred().unwrap()));
+        }
+        if hi < end {
+            kept.push((hi.checked_succ().unwrap(), end));
+        }
+        self.intervals.splice(first..last, kept);
+    }
+
+    #[allow(dead_code)]
+    pub fn union(&self, other: &Self) -> Self {
+        let mut result = self.clone();
+        for range in other.iter() {
+            result.insert(range);
+        }
+        result
+    }
+
+    #[allow(dead_code)]
+    pub fn intersection(&self, other: &Self) -> Self {
+        let mut intervals = Vec::new();
+        let (mut i, mut j) = (0, 0);
+        while i < self.intervals.len() && j < other.intervals.len() {
+            let (a_lo, a_hi) = self.intervals[i];
+            let (b_lo, b_hi) = other.intervals[j];
+            let (lo, hi) = (a_lo.max(b_lo), a_hi.min(b_hi));
+            if lo <= hi {
+                intervals.push((lo, hi));
+            }
+            if a_hi < b_hi {
+                i += 1;
+            } else {
+                j += 1;
+            }
+        }
+        Self { intervals }
+    }
+
+    #[allow(dead_code)]
+    pub fn difference(&self, other: &Self) -> Self {
+        let mut result = self.clone();
+        for range in other.iter() {
+            result.remove(range);
+        }
+        result
+    }
+
+    /// Everything in `bounds` that is not in the set.
+    pub fn complement(&self, bounds: RangeInclusive<T>) -> Self {
+        let mut result = Self::from(bounds);
+        for range in self.iter() {
+            result.remove(range);
+        }
+        result
+    }
+
+    /// The missing stretches between consecutive intervals, in ascending
+    /// order. Nothing before the first or after the last interval counts as a
+    /// gap; use [`IntervalSet::complement`] for that.
+    #[allow(dead_code)]
+    pub fn gaps(&self) -> impl Iterator<Item = RangeInclusive<T>> + '_ {
+        self.intervals.windows(2).map(|w| {
+            // intervals never touch, so both ends exist and lo <= hi
+            w[0].1.checked_succ().unwrap()..=w[1].0.checked_pred().unwrap()
+        })
+    }
+}
+
+impl<T: Endpoint> Default for IntervalSet<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: Endpoint> From<RangeInclusive<T>> for IntervalSet<T> {
+    fn from(range: RangeInclusive<T>) -> Self {
+        let mut set = Self::new();
+        set.insert(range);
+        set
+    }
+}
+
+impl<T: Endpoint> FromIterator<RangeInclusive<T>> for IntervalSet<T> {
+    fn from_iter<I: IntoIterator<Item = RangeInclusive<T>>>(iter: I) -> Self {
+        let mut set = Self::new();
+        for range in iter {
+            set.insert(range);
+        }
+        set
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn ranges(set: &IntervalSet<i32>) -> Vec<RangeInclusive<i32>> {
+        set.iter().collect()
+    }
+
+    #[test]
+    fn test_insert_coalesces() {
+        let mut set: IntervalSet<i32> = [10..=12, 1..=3, 20..=25].into_iter().collect();
+        assert_eq!(ranges(&set), vec![1..=3, 10..=12, 20..=25]);
+
+        set.insert(4..=5);
+        assert_eq!(ranges(&set), vec![1..=5, 10..=12, 20..=25]);
+        set.insert(11..=21);
+        assert_eq!(ranges(&set), vec![1..=5, 10..=25]);
+        set.insert(RangeInclusive::new(7, 6));
+        assert_eq!(ranges(&set), vec![1..=5, 10..=25]);
+        assert_eq!(set.len(), 5 + 16);
+
+        let mut edge = IntervalSet::from(i32::MAX - 1..=i32::MAX);
+        edge.insert(i32::MIN..=i32::MIN);
+        edge.insert(i32::MIN + 1..=0);
+        assert_eq!(ranges(&edge), vec![i32::MIN..=0, i32::MAX - 1..=i32::MAX]);
+    }
+
+    #[test]
+    fn test_remove() {
+        let mut set: IntervalSet<i32> = [1..=10, 20..=30].into_iter().collect();
+        set.remove(5..=5);
+        assert_eq!(ranges(&set), vec![1..=4, 6..=10, 20..=30]);
+        set.remove(8..=25);
+        assert_eq!(ranges(&set), vec![1..=4, 6..=7, 26..=30]);
+        set.remove(0..=100);
+        assert!(set.is_empty());
+    }
+
+    #[test]
+    fn test_set_operations() {
+        let a: IntervalSet<i64> = [0..=10, 20..=30].into_iter().collect();
+        let b: IntervalSet<i64> = [5..=25].into_iter().collect();
+
+        assert_eq!(a.union(&b).iter().collect::<Vec<_>>(), vec![0..=30]);
+        assert_eq!(
+            a.intersection(&b).iter().collect::<Vec<_>>(),
+            vec![5..=10, 20..=25]
+        );
+        assert_eq!(
+            a.difference(&b).iter().collect::<Vec<_>>(),
+            vec![0..=4, 26..=30]
+        );
+        assert_eq!(
+            a.complement(-5..=35).iter().collect::<Vec<_>>(),
+            vec![-5..=-1, 11..=19, 31..=35]
+        );
+        assert_eq!(a.gaps().collect::<Vec<_>>(), vec![11..=19]);
+        assert_eq!(a.hull(), Some(0..=30));
+    }
+
+    #[test]
+    fn test_queries() {
+        let set: IntervalSet<u32> = [2..=4, 8..=9].into_iter().collect();
+        assert!(set.contains(3));
+        assert!(!set.contains(5));
+        assert!(set.covers(&(8..=9)));
+        assert!(!set.covers(&(4..=8)));
+    }
+}
@@ -1,6 +1,18 @@
+mod automaton;
+mod bitgrid;
+mod cycle;
 mod days;
 mod grid;
+mod grid3;
+mod interval;
+mod memo;
+mod netpbm;
+mod parse;
+mod record;
+mod region;
+mod sections;
 
+use automaton::{Automaton, RuleSet};
 use days::day1;
 use days::day10;
 use days::day11;
@@ -24,19 +36,81 @@ use days::day6;
 use days::day7;
 use days::day8;
 use days::day9;
+use record::Frame;
 use std::env::{args, current_dir};
 use std::error::Error;
-use std::path::PathBuf;
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
 
-fn default_input(day: usize, relative: &PathBuf) -> std::io::Result<String> {
-    let path = relative.join("input").join(format!("day{}.txt", day));
-    std::fs::read_to_string(path)
+// Inputs larger than this are streamed to the days that support it rather
+// than read into memory.
+const STREAMING_THRESHOLD: u64 = 64 * 1024 * 1024;
+
+fn default_input(day: usize, relative: &Path) -> PathBuf {
+    relative.join("input").join(format!("day{}.txt", day))
+}
+
+// Runs a text rule set (see `automaton::RuleSet`) over a grid of characters
+// until it stops changing, so rule variants can be tried without recompiling.
+fn run_automaton(args: &[&String]) -> Result<(), Box<dyn Error>> {
+    let (rules, grid, max_steps) = match args {
+        [rules, grid] => (rules, grid, 1000),
+        [rules, grid, max_steps] => (rules, grid, max_steps.parse()?),
+        _ => return Err("usage: adventofcode2022 automaton <rules> <grid> [max steps]".into()),
+    };
+
+    let rules: RuleSet = std::fs::read_to_string(rules)?.parse()?;
+    let grid = Frame::from_text(&std::fs::read_to_string(grid)?, None).grid;
+    let mut automaton = Automaton::new(grid, rules.neighborhood);
+    for generation in 1..=max_steps {
+        let changed = automaton.step(|c, n| rules.apply(c, n));
+        record::frame(|| Frame {
+            grid: automaton.grid().clone(),
+            caption: Some(format!("generation {generation}: {changed} changed")),
+        });
+
+        if changed == 0 {
+            print!("{}", automaton.grid());
+            println!("stable after {generation} generations");
+            return Ok(());
+        }
+    }
+
+    print!("{}", automaton.grid());
+    println!("still changing after {max_steps} generations");
+    Ok(())
+}
+
+// Plans which directories (or files) to delete from the day 7 disk to free
+// a given amount of space.
+fn run_cleanup(args: &[&String]) -> Result<(), Box<dyn Error>> {
+    let mut cleanup = day7::Cleanup::default();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().ok_or(format!("{arg} needs a value"));
+        match arg.as_str() {
+            "--capacity" => cleanup.capacity = value()?.parse()?,
+            "--required" => cleanup.required = value()?.parse()?,
+            "--protect" => cleanup.protected.push(value()?.to_string()),
+            "--files" => cleanup.files = true,
+            "--fewest" => cleanup.objective = day7::Objective::FewestDeletions,
+            "--least-bytes" => cleanup.objective = day7::Objective::LeastBytes,
+            _ => return Err(format!("unknown cleanup option {arg}").into()),
+        }
+    }
+
+    let input = std::fs::read_to_string(default_input(7, &current_dir()?))?;
+    let fs: day7::FileSystem = input.parse()?;
+    print!("{}", cleanup.plan(&fs)?.describe(&fs));
+    Ok(())
 }
 
 fn main() -> Result<(), Box<dyn Error>> {
     let args: Vec<String> = args().collect();
 
     type Part = dyn Fn(&str) -> Result<String, Box<dyn Error>>;
+    type StreamPart = dyn Fn(&mut dyn BufRead) -> Result<String, Box<dyn Error>>;
     let days: Vec<Vec<Box<Part>>> = vec![
         vec![Box::new(day1::part1), Box::new(day1::part2)],
         vec![Box::new(day2::part1), Box::new(day2::part2)],
@@ -62,25 +136,208 @@ fn main() -> Result<(), Box<dyn Error>> {
         vec![Box::new(day22::part1), Box::new(day22::part2)],
         vec![Box::new(day23::part1), Box::new(day23::part2)],
     ];
+    let streaming: Vec<(usize, Vec<Box<StreamPart>>)> = vec![
+        (
+            1,
+            vec![Box::new(day1::part1_stream), Box::new(day1::part2_stream)],
+        ),
+        (
+            6,
+            vec![Box::new(day6::part1_stream), Box::new(day6::part2_stream)],
+        ),
+        (
+            7,
+            vec![Box::new(day7::part1_stream), Box::new(day7::part2_stream)],
+        ),
+        (
+            9,
+            vec![Box::new(day9::part1_stream), Box::new(day9::part2_stream)],
+        ),
+        (
+            10,
+            vec![Box::new(day10::part1_stream), Box::new(day10::part2_stream)],
+        ),
+    ];
+
+    let mut positional: Vec<&String> = Vec::new();
+    let mut recording: Option<&String> = None;
+    let mut fps = 10.0;
+    let mut force_stream = false;
+    let mut flags = args.iter().skip(1);
+    while let Some(arg) = flags.next() {
+        match arg.as_str() {
+            "--record" => recording = Some(flags.next().ok_or("--record needs a path")?),
+            "--fps" => fps = flags.next().ok_or("--fps needs a value")?.parse()?,
+            "--stream" => force_stream = true,
+            "--memo-stats" => memo::report_stats(),
+            _ => positional.push(arg),
+        }
+    }
+
+    match recording.map(String::as_str) {
+        Some("-") => record::start(Box::new(record::AnsiReplay::new(std::io::stdout(), fps))),
+        Some(dir) => record::start(Box::new(record::ImageSequence::new(
+            dir,
+            record::default_palette,
+        )?)),
+        None => {}
+    }
+
+    match positional.first().map(|s| s.as_str()) {
+        Some("automaton") => return run_automaton(&positional[1..]),
+        Some("assignments") => {
+            let input = std::fs::read_to_string(default_input(4, &current_dir()?))?;
+            print!("{}", day4::report(&input)?);
+            return Ok(());
+        }
+        Some("cleanup") => return run_cleanup(&positional[1..]),
+        Some("crane") => {
+            let model = positional.get(1).map_or("9000", |s| s.as_str());
+            let max_lift = positional.get(2).map(|n| n.parse()).transpose()?;
+            let input = std::fs::read_to_string(default_input(5, &current_dir()?))?;
+            print!(
+                "{}",
+                day5::simulate(&input, &mut *day5::crane(model, max_lift)?)?
+            );
+            return Ok(());
+        }
+        Some("crate-top") => {
+            let (Some(item), Some(stack)) = (positional.get(1), positional.get(2)) else {
+                return Err(
+                    "usage: adventofcode2022 crate-top <crate> <stack> [crane] [max lift]".into(),
+                );
+            };
+            let item = item.chars().next().ok_or("no crate given")?;
+            let model = positional.get(3).map_or("9000", |s| s.as_str());
+            let max_lift = positional.get(4).map(|n| n.parse()).transpose()?;
+            let input = std::fs::read_to_string(default_input(5, &current_dir()?))?;
+            let crane = &mut *day5::crane(model, max_lift)?;
+            println!(
+                "{}",
+                day5::first_on_top(&input, item, stack.parse()?, crane)?
+            );
+            return Ok(());
+        }
+        Some("elves") => {
+            let k = match positional.get(1) {
+                Some(k) => k.parse()?,
+                None => 3,
+            };
+            let input = std::fs::read_to_string(default_input(1, &current_dir()?))?;
+            print!("{}", day1::report(&input, k)?);
+            return Ok(());
+        }
+        Some("fs") => {
+            let input = std::fs::read_to_string(default_input(7, &current_dir()?))?;
+            let fs: day7::FileSystem = input.parse()?;
+            match positional.get(1).map(|s| s.as_str()) {
+                Some("du") => {
+                    for (path, size) in fs.du() {
+                        println!("{size}\t{path}");
+                    }
+                }
+                path => {
+                    let path = path.unwrap_or("/");
+                    let node = fs.lookup(path).ok_or(format!("no such file: {path}"))?;
+                    print!("{}", fs.tree(node));
+                }
+            }
+            return Ok(());
+        }
+        Some("sandbox") => {
+            let dir = positional
+                .get(1)
+                .ok_or("usage: adventofcode2022 sandbox <dir>")?;
+            let input = std::fs::read_to_string(default_input(7, &current_dir()?))?;
+            std::fs::create_dir_all(dir)?;
+            day7::materialize(&input.parse()?, Path::new(dir))?;
+            return Ok(());
+        }
+        Some("transcribe") => {
+            let dir = positional
+                .get(1)
+                .ok_or("usage: adventofcode2022 transcribe <dir>")?;
+            print!("{}", day7::transcript(Path::new(dir))?);
+            return Ok(());
+        }
+        Some("unwind") => {
+            let model = positional.get(1).map_or("9000", |s| s.as_str());
+            let max_lift = positional.get(2).map(|n| n.parse()).transpose()?;
+            let input = std::fs::read_to_string(default_input(5, &current_dir()?))?;
+            print!(
+                "{}",
+                day5::unwind(&input, &mut *day5::crane(model, max_lift)?)?
+            );
+            return Ok(());
+        }
+        Some("markers") => {
+            let size = positional
+                .get(1)
+                .ok_or("usage: adventofcode2022 markers <size>")?;
+            let input = File::open(default_input(6, &current_dir()?))?;
+            let mut out = BufWriter::new(std::io::stdout().lock());
+            for position in day6::read_markers(input, size.parse()?)? {
+                writeln!(out, "{}", position?)?;
+            }
+            return Ok(());
+        }
+        Some("rps") => {
+            let rules = match positional.get(1).map(|s| s.as_str()) {
+                Some(name @ ("classic" | "lizard-spock")) => name.to_string(),
+                Some(path) => std::fs::read_to_string(path)?,
+                None => "classic".to_string(),
+            };
+            let input = std::fs::read_to_string(default_input(2, &current_dir()?))?;
+            print!("{}", day2::report(&input, &rules)?);
+            return Ok(());
+        }
+        _ => {}
+    }
 
-    match args.len() {
-        3 => {
-            let day: usize = args[1].parse()?;
-            let part: usize = args[2].parse()?;
-            let input: String = default_input(day, &current_dir()?)?;
+    match positional.len() {
+        2 => {
+            let day: usize = positional[0].parse()?;
+            let part: usize = positional[1].parse()?;
+            let path = default_input(day, &current_dir()?);
 
             let f = days
                 .get(day - 1)
                 .ok_or(format!("invalid day {}", day))?
                 .get(part - 1)
                 .ok_or(format!("invalid part {} for day {}", part, day))?;
+            let stream = streaming
+                .iter()
+                .find(|(d, _)| *d == day)
+                .and_then(|(_, parts)| parts.get(part - 1));
 
-            let result = f(&input)?;
+            let result = match stream {
+                Some(f)
+                    if force_stream || std::fs::metadata(&path)?.len() > STREAMING_THRESHOLD =>
+                {
+                    f(&mut BufReader::new(File::open(&path)?))?
+                }
+                _ if force_stream => return Err(format!("day {day} can't be streamed").into()),
+                _ => f(&std::fs::read_to_string(&path)?)?,
+            };
             println!("Day {} Part {}:\n{}", day, part, result);
             Ok(())
         }
         _ => {
-            println!("Usage: adventofcode2022 day");
+            println!("Usage: adventofcode2022 day part [--stream] [--record <dir>|-] [--fps n]");
+            println!("                                     [--memo-stats]");
+            println!("       adventofcode2022 automaton <rules> <grid> [max steps]");
+            println!("       adventofcode2022 assignments");
+            println!("       adventofcode2022 cleanup [--capacity n] [--required n] [--files]");
+            println!("                            [--fewest|--least-bytes] [--protect path]...");
+            println!("       adventofcode2022 crane [9000|9001|flip-alternate] [max lift]");
+            println!("       adventofcode2022 crate-top <crate> <stack> [crane] [max lift]");
+            println!("       adventofcode2022 elves [k]");
+            println!("       adventofcode2022 fs [du|<path>]");
+            println!("       adventofcode2022 markers <size>");
+            println!("       adventofcode2022 rps [classic|lizard-spock|<rules file>]");
+            println!("       adventofcode2022 sandbox <dir>");
+            println!("       adventofcode2022 transcribe <dir>");
+            println!("       adventofcode2022 unwind [crane] [max lift]");
             Err("invalid arguments".into())
         }
     }
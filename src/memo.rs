@@ -0,0 +1,273 @@
+//! Memo tables for recursive and search-based solvers.
+//!
+//! A [`Memo`] is a hash map that counts how often it was asked for a key it
+//! had (a hit) or didn't have (a miss), and that can optionally be limited to
+//! a number of entries, evicting the least recently used entry when full.
+//!
+//! Recursive solvers usually can't hand the table a closure to compute a
+//! missing value, because the computation needs the table too, so the usual
+//! shape is:
+//!
+//! ```ignore
+//! if let Some(best) = memo.get(&key) {
+//!     return best;
+//! }
+//! let best = ...; // recurse
+//! memo.insert(key, best);
+//! best
+//! ```
+//!
+//! Search states are often a handful of small integers; a [`KeyBuilder`] packs
+//! them into a single `u128` that is cheap to hash and compare.
+//!
+//! Solvers pass their tables' [`Stats`] to [`report`] when they finish; they
+//! are only printed once [`report_stats`] has been called (`--memo-stats`).
+
+use std::{
+    cell::Cell,
+    collections::{BTreeMap, HashMap},
+    fmt::Display,
+    hash::Hash,
+};
+
+pub struct Memo<K, V> {
+    entries: HashMap<K, (V, u64)>,
+    // Only kept when there is a limit: each entry's key by when it was last
+    // used, so the oldest can be found quickly.
+    recency: BTreeMap<u64, K>,
+    limit: Option<usize>,
+    clock: u64,
+    stats: Stats,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Stats {
+    pub hits: u64,
+    pub misses: u64,
+    pub evictions: u64,
+}
+
+/// Packs small unsigned fields into a `u128`, each in its own run of bits.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct KeyBuilder {
+    key: u128,
+    used: u32,
+}
+
+thread_local! {
+    static REPORTING: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Prints the stats every following [`report`] on this thread is given.
+pub fn report_stats() {
+    REPORTING.with(|r| r.set(true));
+}
+
+/// Prints `stats` on stderr, labelled with `table`, if reporting is on.
+pub fn report(table: &str, stats: Stats) {
+    if REPORTING.with(Cell::get) {
+        eprintln!("{table}: {stats}");
+    }
+}
+
+impl<K: Hash + Eq + Clone, V: Clone> Memo<K, V> {
+    pub fn new() -> Self {
+        Self {
+            entries: HashMap::new(),
+            recency: BTreeMap::new(),
+            limit: None,
+            clock: 0,
+            stats: Stats::default(),
+        }
+    }
+
+    /// A table holding at most `limit` entries.
+    pub fn with_limit(limit: usize) -> Self {
+        assert!(limit > 0, "a memo table needs room for at least one entry");
+        Self {
+            limit: Some(limit),
+            ..Self::new()
+        }
+    }
+
+    /// Looks up `key`, counting a hit or a miss.
+    pub fn get(&mut self, key: &K) -> Option<V> {
+        self.clock += 1;
+        let Some((value, used)) = self.entries.get_mut(key) else {
+            self.stats.misses += 1;
+            return None;
+        };
+
+        self.stats.hits += 1;
+        if self.limit.is_some() {
+            let key = self.recency.remove(used).unwrap();
+            self.recency.insert(self.clock, key);
+        }
+        *used = self.clock;
+        Some(value.clone())
+    }
+
+    /// Stores `value` under `key`, evicting the least recently used entry if
+    /// the table is full.
+    pub fn insert(&mut self, key: K, value: V) {
+        self.clock += 1;
+        let Some(limit) = self.limit else {
+            self.entries.insert(key, (value, self.clock));
+            return;
+        };
+
+        if let Some((_, used)) = self.entries.get(&key) {
+            self.recency.remove(used);
+        } else if self.entries.len() == limit {
+            let (_, oldest) = self.recency.pop_first().unwrap();
+            self.entries.remove(&oldest);
+            self.stats.evictions += 1;
+        }
+        self.recency.insert(self.clock, key.clone());
+        self.entries.insert(key, (value, self.clock));
+    }
+
+    /// Returns the value for `key`, computing and storing it first if needed.
+    #[allow(dead_code)]
+    pub fn get_or_insert_with(&mut self, key: K, f: impl FnOnce() -> V) -> V {
+        if let Some(value) = self.get(&key) {
+            return value;
+        }
+        let value = f();
+        self.insert(key, value.clone());
+        value
+    }
+
+    #[allow(dead_code)]
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn stats(&self) -> Stats {
+        self.stats
+    }
+}
+
+impl<K: Hash + Eq + Clone, V: Clone> Default for Memo<K, V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Stats {
+    /// The fraction of lookups that were hits, or 0 if there were none.
+    pub fn hit_rate(&self) -> f64 {
+        match self.hits + self.misses {
+            0 => 0.0,
+            total => self.hits as f64 / total as f64,
+        }
+    }
+}
+
+impl Display for Stats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} hits, {} misses ({:.1}% hit rate), {} evictions",
+            self.hits,
+            self.misses,
+            self.hit_rate() * 100.0,
+            self.evictions
+        )
+    }
+}
+
+impl KeyBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Appends `value` in the next `bits` bits. Panics if the value doesn't
+    /// fit, or if the key would need more than 128 bits, since either would
+    /// let different states share a key.
+    pub fn push(mut self, value: u64, bits: u32) -> Self {
+        assert!(
+            bits <= 64 && self.used + bits <= 128,
+            "key needs more than 128 bits"
+        );
+        assert!(
+            bits == 64 || value >> bits == 0,
+            "{value} does not fit in {bits} bits"
+        );
+        self.key |= (value as u128) << self.used;
+        self.used += bits;
+        self
+    }
+
+    pub fn build(self) -> u128 {
+        self.key
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn fib(n: u64, memo: &mut Memo<u64, u64>) -> u64 {
+        if n < 2 {
+            return n;
+        }
+        if let Some(v) = memo.get(&n) {
+            return v;
+        }
+        let v = fib(n - 1, memo) + fib(n - 2, memo);
+        memo.insert(n, v);
+        v
+    }
+
+    #[test]
+    fn test_memo_stats() {
+        let mut memo = Memo::new();
+        assert_eq!(fib(50, &mut memo), 12586269025);
+        assert_eq!(
+            memo.stats(),
+            Stats {
+                hits: 47,
+                misses: 49,
+                evictions: 0
+            }
+        );
+        assert_eq!(memo.len(), 49);
+    }
+
+    #[test]
+    fn test_lru_eviction() {
+        let mut memo = Memo::with_limit(2);
+        memo.insert("a", 1);
+        memo.insert("b", 2);
+        assert_eq!(memo.get(&"a"), Some(1));
+        memo.insert("c", 3);
+
+        // b was the least recently used
+        assert_eq!(memo.get(&"b"), None);
+        assert_eq!(memo.get(&"a"), Some(1));
+        assert_eq!(memo.get_or_insert_with("c", || 30), 3);
+        assert_eq!(memo.len(), 2);
+        assert_eq!(memo.stats().evictions, 1);
+        assert_eq!(
+            memo.stats().to_string(),
+            "3 hits, 1 misses (75.0% hit rate), 1 evictions"
+        );
+    }
+
+    #[test]
+    fn test_key_builder() {
+        let key = KeyBuilder::new().push(3, 2).push(0, 4).push(u64::MAX, 64);
+        assert_eq!(key.build(), 3 | (u64::MAX as u128) << 6);
+        assert_ne!(
+            KeyBuilder::new().push(1, 4).push(2, 4).build(),
+            KeyBuilder::new().push(2, 4).push(1, 4).build()
+        );
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_key_builder_overflow() {
+        KeyBuilder::new().push(16, 4);
+    }
+}
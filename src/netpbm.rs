@@ -0,0 +1,420 @@
+//! Netpbm images of grids: PBM (black and white), PGM (greyscale) and PPM
+//! (colour). Images are written in the compact binary variants (P4, P5, P6);
+//! both the binary and the plain text variants (P1, P2, P3) can be read back,
+//! so fixtures can be drawn in an image editor.
+
+use std::{
+    error::Error,
+    io::{self, Write},
+};
+
+use crate::grid::Grid;
+
+type Result<T> = std::result::Result<T, Box<dyn Error>>;
+
+impl<T: PartialEq + Default + Clone> Grid<T> {
+    /// Writes a PBM image, drawing cells for which `black` holds in black.
+    #[allow(dead_code)]
+    pub fn write_pbm(&self, out: &mut impl Write, black: impl Fn(&T) -> bool) -> io::Result<()> {
+        let (width, height) = self.dimensions();
+        write!(out, "P4\n{width} {height}\n")?;
+        for row in self.rows() {
+            let mut packed = vec![0u8; width.div_ceil(8)];
+            for (col, cell) in row.iter().enumerate() {
+                if black(cell) {
+                    packed[col / 8] |= 0x80 >> (col % 8);
+                }
+            }
+            out.write_all(&packed)?;
+        }
+        Ok(())
+    }
+
+    /// Writes a PGM image using `level` as each cell's grey level. The
+    /// brightest level in the grid is drawn white.
+    #[allow(dead_code)]
+    pub fn write_pgm(&self, out: &mut impl Write, level: impl Fn(&T) -> u16) -> io::Result<()> {
+        let (width, height) = self.dimensions();
+        let levels: Vec<u16> = self.rows().iter().flatten().map(level).collect();
+        let maxval = levels.iter().copied().max().unwrap_or(0).max(1);
+        write!(out, "P5\n{width} {height}\n{maxval}\n")?;
+        write_samples(out, &levels, maxval)
+    }
+
+    /// Writes a PPM image, colouring each cell with the RGB value returned by
+    /// `palette`.
+    pub fn write_ppm(
+        &self,
+        out: &mut impl Write,
+        palette: impl Fn(&T) -> [u8; 3],
+    ) -> io::Result<()> {
+        let (width, height) = self.dimensions();
+        write!(out, "P6\n{width} {height}\n255\n")?;
+        for cell in self.rows().iter().flatten() {
+            out.write_all(&palette(cell))?;
+        }
+        Ok(())
+    }
+
+    fn dimensions(&self) -> (usize, usize) {
+        let rows = self.rows();
+        (rows.first().map_or(0, |r| r.len()), rows.len())
+    }
+}
+
+impl Grid<bool> {
+    /// Reads a PBM image; black pixels become `true`.
+    #[allow(dead_code)]
+    pub fn read_pbm(input: &[u8]) -> Result<Self> {
+        let image = Image::parse(input)?;
+        if image.kind != 1 && image.kind != 4 {
+            return Err(format!("expected a PBM image, found P{}", image.kind).into());
+        }
+        Ok(image.into_grid(|s| s == 1))
+    }
+}
+
+impl Grid<u16> {
+    /// Reads a PGM image as its raw grey levels.
+    #[allow(dead_code)]
+    pub fn read_pgm(input: &[u8]) -> Result<Self> {
+        let image = Image::parse(input)?;
+        if image.kind != 2 && image.kind != 5 {
+            return Err(format!("expected a PGM image, found P{}", image.kind).into());
+        }
+        Ok(image.into_grid(|s| s))
+    }
+}
+
+impl<T: PartialEq + Default + Clone> Grid<T> {
+    /// Reads a PPM image, turning each colour back into a cell with `decode`.
+    /// Colours are scaled to 0-255 first; any colour `decode` rejects is an
+    /// error.
+    #[allow(dead_code)]
+    pub fn read_ppm(input: &[u8], decode: impl Fn([u8; 3]) -> Option<T>) -> Result<Self> {
+        let image = Image::parse(input)?;
+        if image.kind != 3 && image.kind != 6 {
+            return Err(format!("expected a PPM image, found P{}", image.kind).into());
+        }
+
+        let maxval = image.maxval as u32;
+        let scale = |s: u16| (s as u32 * 255 / maxval) as u8;
+        let width = image.width;
+        let mut cells = Vec::with_capacity(image.width * image.height);
+        for (i, rgb) in image.samples.chunks(3).enumerate() {
+            let rgb = [scale(rgb[0]), scale(rgb[1]), scale(rgb[2])];
+            let cell = decode(rgb).ok_or_else(|| {
+                format!(
+                    "unknown colour {rgb:?} at row {} column {}",
+                    i / width,
+                    i % width
+                )
+            })?;
+            cells.push(cell);
+        }
+
+        Ok(Grid::from_vec(
+            cells.chunks(width).map(|r| r.to_vec()).collect(),
+        ))
+    }
+}
+
+fn write_samples(out: &mut impl Write, samples: &[u16], maxval: u16) -> io::Result<()> {
+    if maxval < 256 {
+        out.write_all(&samples.iter().map(|s| *s as u8).collect::<Vec<u8>>())
+    } else {
+        out.write_all(
+            &samples
+                .iter()
+                .flat_map(|s| s.to_be_bytes())
+                .collect::<Vec<u8>>(),
+        )
+    }
+}
+
+/// A decoded image: the `N` from its `PN` magic number, and every sample in
+/// raster order (one per pixel, or three per pixel for PPM).
+struct Image {
+    kind: u8,
+    width: usize,
+    height: usize,
+    maxval: u16,
+    samples: Vec<u16>,
+}
+
+impl Image {
+    fn parse(input: &[u8]) -> Result<Self> {
+        let mut cursor = Cursor { input, pos: 0 };
+        let kind = match cursor.token()? {
+            b"P1" => 1,
+            b"P2" => 2,
+            b"P3" => 3,
+            b"P4" => 4,
+            b"P5" => 5,
+            b"P6" => 6,
+            magic => {
+                return Err(format!(
+                    "unsupported magic number '{}'",
+                    String::from_utf8_lossy(magic)
+                )
+                .into())
+            }
+        };
+
+        let width: usize = cursor.number()?;
+        let height: usize = cursor.number()?;
+        let maxval: u16 = if kind == 1 || kind == 4 {
+            1
+        } else {
+            cursor.number()?
+        };
+        if width == 0 || height == 0 {
+            return Err(format!("image must be at least 1x1, found {width}x{height}").into());
+        }
+        if maxval == 0 {
+            return Err("maxval must be at least 1".into());
+        }
+
+        let channels = if kind == 3 || kind == 6 { 3 } else { 1 };
+        let count = width
+            .checked_mul(height)
+            .and_then(|n| n.checked_mul(channels))
+            .ok_or_else(|| format!("image too large: {width}x{height}"))?;
+        // a plain sample takes at least a byte, so this bounds what gets
+        // allocated before the samples are read
+        if kind <= 3 && count > cursor.remaining() {
+            return Err(format!(
+                "expected {count} samples, but only {} bytes follow",
+                cursor.remaining()
+            )
+            .into());
+        }
+        let samples = match kind {
+            1 => cursor.bits(count)?,
+            2 | 3 => (0..count)
+                .map(|_| cursor.number())
+                .collect::<Result<Vec<u16>>>()?,
+            4 => cursor.raw_bits(width, height)?,
+            _ => cursor.raw_samples(count, maxval)?,
+        };
+
+        if let Some(sample) = samples.iter().find(|s| **s > maxval) {
+            return Err(format!("sample {sample} exceeds maxval {maxval}").into());
+        }
+
+        Ok(Self {
+            kind,
+            width,
+            height,
+            maxval,
+            samples,
+        })
+    }
+
+    fn into_grid<T: PartialEq + Default + Clone>(self, decode: impl Fn(u16) -> T) -> Grid<T> {
+        Grid::from_vec(
+            self.samples
+                .chunks(self.width)
+                .map(|row| row.iter().copied().map(&decode).collect())
+                .collect(),
+        )
+    }
+}
+
+struct Cursor<'a> {
+    input: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Cursor<'a> {
+    /// Skips whitespace and `#` comments, then returns the next run of
+    /// non-whitespace bytes.
+    fn token(&mut self) -> Result<&'a [u8]> {
+        loop {
+            match self.input.get(self.pos) {
+                Some(b'#') => {
+                    while !matches!(self.input.get(self.pos), None | Some(b'\n')) {
+                        self.pos += 1;
+                    }
+                }
+                Some(c) if c.is_ascii_whitespace() => self.pos += 1,
+                Some(_) => break,
+                None => return Err("unexpected end of image header".into()),
+            }
+        }
+
+        let start = self.pos;
+        while matches!(self.input.get(self.pos), Some(c) if !c.is_ascii_whitespace()) {
+            self.pos += 1;
+        }
+        Ok(&self.input[start..self.pos])
+    }
+
+    fn number<N: std::str::FromStr>(&mut self) -> Result<N> {
+        let offset = self.pos;
+        let token = self.token()?;
+        std::str::from_utf8(token)
+            .ok()
+            .and_then(|t| t.parse().ok())
+            .ok_or_else(|| {
+                format!(
+                    "invalid number '{}' near byte {offset}",
+                    String::from_utf8_lossy(token)
+                )
+                .into()
+            })
+    }
+
+    fn remaining(&self) -> usize {
+        self.input.len().saturating_sub(self.pos)
+    }
+
+    /// Reads plain PBM pixels, which may or may not be separated by spaces.
+    fn bits(&mut self, count: usize) -> Result<Vec<u16>> {
+        let mut bits = Vec::with_capacity(count);
+        while bits.len() < count {
+            for c in self.token()? {
+                match c {
+                    b'0' => bits.push(0),
+                    b'1' => bits.push(1),
+                    _ => return Err(format!("invalid PBM pixel '{}'", *c as char).into()),
+                }
+            }
+        }
+        bits.truncate(count);
+        Ok(bits)
+    }
+
+    /// Returns the binary raster that follows the single whitespace byte
+    /// ending the header.
+    fn raster(&mut self, len: usize) -> Result<&'a [u8]> {
+        let start = self.pos + 1;
+        start
+            .checked_add(len)
+            .and_then(|end| self.input.get(start..end))
+            .ok_or_else(|| format!("expected {len} bytes of image data").into())
+    }
+
+    fn raw_bits(&mut self, width: usize, height: usize) -> Result<Vec<u16>> {
+        let row_bytes = width.div_ceil(8);
+        let len = row_bytes
+            .checked_mul(height)
+            .ok_or("image data too large")?;
+        let raster = self.raster(len)?;
+        Ok(raster
+            .chunks(row_bytes)
+            .take(height)
+            .flat_map(|row| (0..width).map(move |col| ((row[col / 8] << (col % 8)) >> 7) as u16))
+            .collect())
+    }
+
+    fn raw_samples(&mut self, count: usize, maxval: u16) -> Result<Vec<u16>> {
+        if maxval < 256 {
+            Ok(self.raster(count)?.iter().map(|b| *b as u16).collect())
+        } else {
+            Ok(self
+                .raster(count.checked_mul(2).ok_or("image data too large")?)?
+                .chunks(2)
+                .map(|b| u16::from_be_bytes([b[0], b[1]]))
+                .collect())
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn checkerboard() -> Grid<bool> {
+        Grid::from_vec(
+            (0..3)
+                .map(|r| (0..10).map(|c| (r + c) % 2 == 0).collect())
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn test_pbm_round_trip() {
+        let grid = checkerboard();
+        let mut out = Vec::new();
+        grid.write_pbm(&mut out, |b| *b).unwrap();
+        assert_eq!(&out[..8], b"P4\n10 3\n");
+        assert_eq!(out.len(), 8 + 2 * 3);
+
+        let read = Grid::read_pbm(&out).unwrap();
+        assert_eq!(read.rows(), grid.rows());
+    }
+
+    #[test]
+    fn test_read_plain_pbm() {
+        let input = b"P1\n# a comment\n3 2\n101\n0 1 0\n";
+        let grid = Grid::read_pbm(input).unwrap();
+        assert_eq!(
+            grid.rows(),
+            &vec![vec![true, false, true], vec![false, true, false]]
+        );
+    }
+
+    #[test]
+    fn test_pgm_round_trip() {
+        let grid = Grid::from_vec(vec![vec![0u16, 300], vec![25, 1000]]);
+        let mut out = Vec::new();
+        grid.write_pgm(&mut out, |v| *v).unwrap();
+        assert_eq!(Grid::read_pgm(&out).unwrap().rows(), grid.rows());
+
+        let plain = Grid::read_pgm(b"P2 2 1 15 3 15").unwrap();
+        assert_eq!(plain.rows(), &vec![vec![3, 15]]);
+    }
+
+    #[test]
+    fn test_ppm_round_trip() {
+        let grid = Grid::from_vec(vec![vec!['#', '.', 'o']]);
+        let palette = |c: &char| match c {
+            '#' => [128, 128, 128],
+            'o' => [255, 200, 0],
+            _ => [0, 0, 0],
+        };
+        let decode = |rgb: [u8; 3]| match rgb {
+            [128, 128, 128] => Some('#'),
+            [255, 200, 0] => Some('o'),
+            [0, 0, 0] => Some('.'),
+            _ => None,
+        };
+
+        let mut out = Vec::new();
+        grid.write_ppm(&mut out, palette).unwrap();
+        assert_eq!(Grid::read_ppm(&out, decode).unwrap().rows(), grid.rows());
+
+        let err = Grid::read_ppm(b"P3 1 1 255 1 2 3", decode).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "unknown colour [1, 2, 3] at row 0 column 0"
+        );
+    }
+
+    #[test]
+    fn test_read_errors() {
+        assert!(Grid::read_pbm(b"P5 1 1 255 x").is_err());
+        assert!(Grid::read_pgm(b"P2 2 2 10 1 2 3").is_err());
+        assert!(Grid::read_pgm(b"P2 1 1 10 11").is_err());
+        assert!(Grid::read_pbm(b"P7 1 1").is_err());
+
+        // huge sizes fail before anything that size is allocated
+        let error = |input: &[u8]| Grid::read_pgm(input).unwrap_err().to_string();
+        assert_eq!(
+            error(b"P2 99999999999 99999999999 10 1"),
+            "image too large: 99999999999x99999999999"
+        );
+        assert_eq!(
+            error(b"P2 100000 100000 10 1 2"),
+            "expected 10000000000 samples, but only 4 bytes follow"
+        );
+        assert!(Grid::read_pbm(b"P1 100000 100000 0").is_err());
+        assert!(Grid::read_pbm(b"P4 100000 100000 x").is_err());
+        assert!(error(b"P5 100000 100000 255 x").starts_with("expected 10000000000 bytes"));
+        assert_eq!(
+            error(b"P5 4611686018427387904 2 65535 x"),
+            "image data too large"
+        );
+    }
+}
@@ -0,0 +1,381 @@
+//! A small parser-combinator toolkit for the puzzle input formats.
+//!
+//! A parser is anything that takes an [`Input`] and either returns a value,
+//! advancing past what it consumed, or fails with a [`ParseError`] saying
+//! where in the text it failed and what it expected to find there. Plain
+//! functions of the form `fn(&mut Input) -> Parsed<T>` are parsers, so a
+//! day's format is usually written as a handful of such functions that call
+//! the building blocks here in sequence:
+//!
+//! ```ignore
+//! fn point(input: &mut Input) -> Parsed<Point> {
+//!     let x = field("x=", signed()).parse(input)?;
+//!     let y = field(", y=", signed()).parse(input)?;
+//!     Ok(Point { x, y })
+//! }
+//!
+//! let points = parse_all(text, lines(point))?;
+//! ```
+
+use std::{error::Error, fmt::Display, str::FromStr};
+
+use crate::sections::sections;
+
+pub type Parsed<T> = Result<T, ParseError>;
+
+/// Text being parsed, and how far into it we are.
+#[derive(Debug, Clone, Copy)]
+pub struct Input<'a> {
+    text: &'a str,
+    pos: usize,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct ParseError {
+    /// 1-based line number.
+    pub line: usize,
+    /// 1-based column, counted in characters.
+    pub column: usize,
+    pub expected: String,
+    /// The rest of the line at the failure point.
+    pub found: String,
+}
+
+pub trait Parser<'a, T> {
+    fn parse(&self, input: &mut Input<'a>) -> Parsed<T>;
+}
+
+impl<'a, T, F> Parser<'a, T> for F
+where
+    F: Fn(&mut Input<'a>) -> Parsed<T>,
+{
+    fn parse(&self, input: &mut Input<'a>) -> Parsed<T> {
+        self(input)
+    }
+}
+
+impl<'a> Input<'a> {
+    pub fn new(text: &'a str) -> Self {
+        Self { text, pos: 0 }
+    }
+
+    /// The text that has not been consumed yet.
+    pub fn rest(&self) -> &'a str {
+        &self.text[self.pos..]
+    }
+
+    pub fn at_end(&self) -> bool {
+        self.pos == self.text.len()
+    }
+
+    /// The 1-based line and column of the current position.
+    pub fn position(&self) -> (usize, usize) {
+        let before = &self.text[..self.pos];
+        let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+        (
+            before.matches('\n').count() + 1,
+            before[line_start..].chars().count() + 1,
+        )
+    }
+
+    /// An error at the current position.
+    pub fn error(&self, expected: impl Into<String>) -> ParseError {
+        let (line, column) = self.position();
+        ParseError {
+            line,
+            column,
+            expected: expected.into(),
+            found: self.rest().lines().next().unwrap_or("").to_string(),
+        }
+    }
+
+    /// Consumes the longest prefix whose characters all satisfy `pred`.
+    fn take_while(&mut self, pred: impl Fn(char) -> bool) -> &'a str {
+        let rest = self.rest();
+        let len = rest.find(|c| !pred(c)).unwrap_or(rest.len());
+        self.pos += len;
+        &rest[..len]
+    }
+}
+
+impl Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "line {}, column {}: expected {}, found ",
+            self.line, self.column, self.expected
+        )?;
+        if self.found.is_empty() {
+            write!(f, "end of line")
+        } else {
+            write!(f, "'{}'", self.found)
+        }
+    }
+}
+
+impl Error for ParseError {}
+
+/// Runs `parser` over the whole of `text`. Only whitespace may be left over.
+pub fn parse_all<'a, T>(text: &'a str, parser: impl Parser<'a, T>) -> Parsed<T> {
+    let mut input = Input::new(text);
+    let value = parser.parse(&mut input)?;
+    input.take_while(char::is_whitespace);
+    if input.at_end() {
+        Ok(value)
+    } else {
+        Err(input.error("end of input"))
+    }
+}
+
+/// Exactly the text `s`.
+pub fn literal<'a>(s: &'static str) -> impl Parser<'a, ()> {
+    move |input: &mut Input<'a>| {
+        if input.rest().starts_with(s) {
+            input.pos += s.len();
+            Ok(())
+        } else {
+            Err(input.error(format!("'{s}'")))
+        }
+    }
+}
+
+/// A run of decimal digits.
+pub fn unsigned<'a, T: FromStr>() -> impl Parser<'a, T> {
+    move |input: &mut Input<'a>| {
+        let start = *input;
+        let digits = input.take_while(|c| c.is_ascii_digit());
+        digits.parse().map_err(|_| {
+            *input = start;
+            input.error("an unsigned integer")
+        })
+    }
+}
+
+/// A run of decimal digits with an optional leading `+` or `-`.
+pub fn signed<'a, T: FromStr>() -> impl Parser<'a, T> {
+    move |input: &mut Input<'a>| {
+        let start = *input;
+        let rest = input.rest();
+        let sign = usize::from(rest.starts_with(['+', '-']));
+        input.pos += sign;
+        let digits = input.take_while(|c| c.is_ascii_digit());
+        rest[..sign + digits.len()].parse().map_err(|_| {
+            *input = start;
+            input.error("an integer")
+        })
+    }
+}
+
+/// A letter followed by any number of letters, digits and underscores.
+pub fn identifier<'a>() -> impl Parser<'a, &'a str> {
+    move |input: &mut Input<'a>| {
+        if !input.rest().starts_with(|c: char| c.is_alphabetic()) {
+            return Err(input.error("an identifier"));
+        }
+        Ok(input.take_while(|c| c.is_alphanumeric() || c == '_'))
+    }
+}
+
+/// A line break, either `\n` or `\r\n`.
+pub fn newline<'a>() -> impl Parser<'a, ()> {
+    move |input: &mut Input<'a>| {
+        let rest = input.rest();
+        if rest.starts_with('\n') {
+            input.pos += 1;
+        } else if rest.starts_with("\r\n") {
+            input.pos += 2;
+        } else {
+            return Err(input.error("end of line"));
+        }
+        Ok(())
+    }
+}
+
+/// A `label` followed by a `value`, e.g. `field("flow rate=", signed())`.
+/// At the start of a line, indentation before the label is skipped, so
+/// indented lines can be read without caring how deeply they are indented.
+pub fn field<'a, T>(label: &'static str, value: impl Parser<'a, T>) -> impl Parser<'a, T> {
+    let label = literal(label);
+    move |input: &mut Input<'a>| {
+        if input.text[..input.pos].ends_with('\n') || input.pos == 0 {
+            input.take_while(|c| c == ' ' || c == '\t');
+        }
+        label.parse(input)?;
+        value.parse(input)
+    }
+}
+
+/// Transforms a parser's result.
+pub fn map<'a, T, U>(parser: impl Parser<'a, T>, f: impl Fn(T) -> U) -> impl Parser<'a, U> {
+    move |input: &mut Input<'a>| parser.parse(input).map(&f)
+}
+
+/// Checks a parser's result. If `f` rejects it, the error points at the
+/// start of the rejected text.
+pub fn try_map<'a, T, U>(
+    parser: impl Parser<'a, T>,
+    f: impl Fn(T) -> Result<U, String>,
+) -> impl Parser<'a, U> {
+    move |input: &mut Input<'a>| {
+        let start = *input;
+        f(parser.parse(input)?).map_err(|e| start.error(e))
+    }
+}
+
+/// Tries `first`, and if it fails without being able to continue, `second`
+/// from the same place. When both fail, the error from whichever got further
+/// is reported.
+pub fn or<'a, T>(first: impl Parser<'a, T>, second: impl Parser<'a, T>) -> impl Parser<'a, T> {
+    move |input: &mut Input<'a>| {
+        let start = *input;
+        first.parse(input).or_else(|e1| {
+            *input = start;
+            second.parse(input).map_err(|e2| {
+                if (e2.line, e2.column) >= (e1.line, e1.column) {
+                    e2
+                } else {
+                    e1
+                }
+            })
+        })
+    }
+}
+
+/// One or more `item`s with a `separator` between each pair. The list ends
+/// at a separator if the following item fails before consuming anything.
+pub fn separated<'a, T, S>(
+    item: impl Parser<'a, T>,
+    separator: impl Parser<'a, S>,
+) -> impl Parser<'a, Vec<T>> {
+    move |input: &mut Input<'a>| {
+        let mut items = vec![item.parse(input)?];
+        loop {
+            let before = *input;
+            if separator.parse(input).is_err() {
+                *input = before;
+                return Ok(items);
+            }
+            let after = *input;
+            match item.parse(input) {
+                Ok(value) => items.push(value),
+                // nothing after the separator looked like an item, so leave
+                // the separator for whatever comes after the list
+                Err(e) if (e.line, e.column) == after.position() => {
+                    *input = before;
+                    return Ok(items);
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+}
+
+/// One `item` per line.
+pub fn lines<'a, T>(item: impl Parser<'a, T>) -> impl Parser<'a, Vec<T>> {
+    separated(item, newline())
+}
+
+/// Blocks of lines separated by blank lines, split the way
+/// [`sections`] splits them, each read in full by `item`. This reads the rest
+/// of the input, and errors give their position in the whole text.
+pub fn blocks<'a, T>(item: impl Parser<'a, T>) -> impl Parser<'a, Vec<T>> {
+    move |input: &mut Input<'a>| {
+        let rest = input.rest();
+        let mut items = Vec::new();
+        for section in sections(rest) {
+            // sections borrow from `rest`, so this is where each one starts
+            let start = input.pos + (section.text.as_ptr() as usize - rest.as_ptr() as usize);
+            let mut block = Input {
+                text: &input.text[..start + section.text.len()],
+                pos: start,
+            };
+            items.push(item.parse(&mut block)?);
+            block.take_while(char::is_whitespace);
+            if !block.at_end() {
+                return Err(block.error("end of block"));
+            }
+        }
+
+        if items.is_empty() {
+            return Err(input.error("a block"));
+        }
+        input.pos = input.text.len();
+        Ok(items)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn pair(input: &mut Input) -> Parsed<(String, i32)> {
+        let name = identifier().parse(input)?;
+        let value = field(" = ", signed()).parse(input)?;
+        Ok((name.to_string(), value))
+    }
+
+    #[test]
+    fn test_primitives() {
+        assert_eq!(parse_all("-42", signed::<i32>()), Ok(-42));
+        assert_eq!(parse_all("+7", signed::<i32>()), Ok(7));
+        assert_eq!(parse_all("17", unsigned::<u8>()), Ok(17));
+        assert_eq!(parse_all("ab_1 x", identifier()).unwrap_err().column, 6);
+        assert_eq!(
+            parse_all("a = 1, b = -2", separated(pair, literal(", "))),
+            Ok(vec![("a".to_string(), 1), ("b".to_string(), -2)])
+        );
+    }
+
+    #[test]
+    fn test_blocks_and_lines() {
+        let text = "a = 1\r\nb = 2\n  \n\nc = 3\n";
+        assert_eq!(
+            parse_all(text, blocks(lines(pair))).unwrap(),
+            vec![
+                vec![("a".to_string(), 1), ("b".to_string(), 2)],
+                vec![("c".to_string(), 3)]
+            ]
+        );
+
+        let error = parse_all("a = 1\r\n \t\r\n\r\nb = x\n", blocks(lines(pair))).unwrap_err();
+        assert_eq!((error.line, error.column), (4, 5));
+        let error = parse_all("a = 1\nb = 2 c\n\nc = 3", blocks(lines(pair))).unwrap_err();
+        assert_eq!(
+            (error.line, error.column, error.found.as_str()),
+            (2, 7, "c")
+        );
+        assert!(parse_all("\n  \n", blocks(lines(pair))).is_err());
+    }
+
+    #[test]
+    fn test_or() {
+        let sign = || or(map(literal("+"), |_| 1), map(literal("-"), |_| -1));
+        assert_eq!(parse_all("-", sign()), Ok(-1));
+        assert_eq!(parse_all("*", sign()).unwrap_err().expected, "'-'");
+    }
+
+    #[test]
+    fn test_error_positions() {
+        let err = parse_all("a = 1\nb = x\n", lines(pair)).unwrap_err();
+        assert_eq!(
+            err,
+            ParseError {
+                line: 2,
+                column: 5,
+                expected: "an integer".to_string(),
+                found: "x".to_string(),
+            }
+        );
+        assert_eq!(
+            err.to_string(),
+            "line 2, column 5: expected an integer, found 'x'"
+        );
+
+        let err = parse_all("a = 1\nb = 2 c", lines(pair)).unwrap_err();
+        assert_eq!((err.line, err.column), (2, 7));
+        assert_eq!(
+            parse_all("a = 1\nb", lines(pair)).unwrap_err().to_string(),
+            "line 2, column 2: expected ' = ', found end of line"
+        );
+    }
+}
@@ -0,0 +1,204 @@
+//! Frame recording for simulations. Solvers call [`frame`] once per step; when
+//! the runner was started with `--record`, each frame is passed to the active
+//! [`FrameSink`], otherwise the call does nothing and the snapshot is never
+//! built.
+
+use std::{
+    cell::RefCell,
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::PathBuf,
+    thread,
+    time::Duration,
+};
+
+use crate::grid::Grid;
+
+pub struct Frame {
+    pub grid: Grid<char>,
+    pub caption: Option<String>,
+}
+
+pub trait FrameSink {
+    fn write_frame(&mut self, frame: &Frame) -> io::Result<()>;
+}
+
+/// Plays frames back in a terminal, redrawing the screen for each one.
+pub struct AnsiReplay<W: Write> {
+    out: W,
+    delay: Duration,
+}
+
+/// Writes each frame to its own numbered PPM file in a directory.
+pub struct ImageSequence {
+    dir: PathBuf,
+    count: usize,
+    palette: fn(char) -> [u8; 3],
+}
+
+thread_local! {
+    static RECORDER: RefCell<Option<Box<dyn FrameSink>>> = RefCell::new(None);
+}
+
+/// Sends every following frame on this thread to `sink`.
+pub fn start(sink: Box<dyn FrameSink>) {
+    RECORDER.with(|r| r.replace(Some(sink)));
+}
+
+/// Records a frame if recording is active. `snapshot` is only called when the
+/// frame will be used. If the sink fails, recording stops and the error is
+/// reported on stderr so the solver can carry on.
+pub fn frame(snapshot: impl FnOnce() -> Frame) {
+    RECORDER.with(|r| {
+        let mut recorder = r.borrow_mut();
+        if let Some(sink) = recorder.as_mut() {
+            if let Err(e) = sink.write_frame(&snapshot()) {
+                eprintln!("recording stopped: {e}");
+                recorder.take();
+            }
+        }
+    })
+}
+
+impl Frame {
+    /// Builds a frame from rendered text, padding short lines with spaces.
+    pub fn from_text(text: &str, caption: Option<String>) -> Self {
+        let width = text.lines().map(|l| l.chars().count()).max().unwrap_or(0);
+        let rows: Vec<Vec<char>> = text
+            .lines()
+            .map(|l| {
+                let mut row: Vec<char> = l.chars().collect();
+                row.resize(width, ' ');
+                row
+            })
+            .collect();
+
+        Self {
+            grid: if rows.is_empty() {
+                Grid::new(0, 0)
+            } else {
+                Grid::from_vec(rows)
+            },
+            caption,
+        }
+    }
+}
+
+impl<W: Write> AnsiReplay<W> {
+    /// Shows `fps` frames per second; zero plays as fast as possible.
+    pub fn new(out: W, fps: f64) -> Self {
+        Self {
+            out,
+            delay: if fps > 0.0 {
+                Duration::from_secs_f64(1.0 / fps)
+            } else {
+                Duration::ZERO
+            },
+        }
+    }
+}
+
+impl<W: Write> FrameSink for AnsiReplay<W> {
+    fn write_frame(&mut self, frame: &Frame) -> io::Result<()> {
+        // clear the screen and move the cursor to the top left
+        write!(self.out, "\x1b[2J\x1b[H{}", frame.grid)?;
+        if let Some(caption) = &frame.caption {
+            writeln!(self.out, "{caption}")?;
+        }
+        self.out.flush()?;
+        thread::sleep(self.delay);
+        Ok(())
+    }
+}
+
+impl ImageSequence {
+    /// Writes `frame-00000.ppm`, `frame-00001.ppm`, ... into `dir`, creating
+    /// it if needed. Captions are written alongside as `captions.txt`.
+    pub fn new(dir: impl Into<PathBuf>, palette: fn(char) -> [u8; 3]) -> io::Result<Self> {
+        let dir = dir.into();
+        std::fs::create_dir_all(&dir)?;
+        File::create(dir.join("captions.txt"))?;
+        Ok(Self {
+            dir,
+            count: 0,
+            palette,
+        })
+    }
+}
+
+impl FrameSink for ImageSequence {
+    fn write_frame(&mut self, frame: &Frame) -> io::Result<()> {
+        let name = format!("frame-{:05}.ppm", self.count);
+        let mut out = BufWriter::new(File::create(self.dir.join(&name))?);
+        frame.grid.write_ppm(&mut out, |c| (self.palette)(*c))?;
+        out.flush()?;
+
+        if let Some(caption) = &frame.caption {
+            let mut captions = File::options()
+                .append(true)
+                .open(self.dir.join("captions.txt"))?;
+            writeln!(captions, "{name}: {caption}")?;
+        }
+
+        self.count += 1;
+        Ok(())
+    }
+}
+
+/// Draws blanks dark, walls and rock light grey, and gives every other
+/// character its own colour.
+pub fn default_palette(c: char) -> [u8; 3] {
+    match c {
+        ' ' | '.' | '\0' => [16, 16, 16],
+        '#' => [200, 200, 200],
+        c => {
+            let n = c as u32;
+            [
+                (64 + (n * 97) % 192) as u8,
+                (64 + (n * 57) % 192) as u8,
+                (64 + (n * 31) % 192) as u8,
+            ]
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::rc::Rc;
+
+    struct Collect(Rc<RefCell<Vec<String>>>);
+
+    impl FrameSink for Collect {
+        fn write_frame(&mut self, frame: &Frame) -> io::Result<()> {
+            self.0.borrow_mut().push(format!(
+                "{}{}",
+                frame.grid,
+                frame.caption.as_deref().unwrap_or("")
+            ));
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_frames_only_built_when_recording() {
+        frame(|| panic!("snapshot taken without a recorder"));
+
+        let frames = Rc::new(RefCell::new(Vec::new()));
+        start(Box::new(Collect(frames.clone())));
+        frame(|| Frame::from_text("#.\n#", Some("step 1".to_string())));
+        RECORDER.with(|r| r.take());
+
+        assert_eq!(*frames.borrow(), vec!["#.\n# \nstep 1".to_string()]);
+    }
+
+    #[test]
+    fn test_ansi_replay() {
+        let mut out = Vec::new();
+        let mut replay = AnsiReplay::new(&mut out, 0.0);
+        replay
+            .write_frame(&Frame::from_text("ab\ncd", None))
+            .unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), "\x1b[2J\x1b[Hab\ncd\n");
+    }
+}
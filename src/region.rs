@@ -0,0 +1,145 @@
+//! Region analysis over any space whose cells can list their neighbours, such
+//! as a [`Grid`](crate::grid::Grid) or a set of voxels.
+
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    hash::Hash,
+};
+
+/// The result of labelling connected components: every cell's component index,
+/// and the number of cells in each component.
+#[derive(Debug)]
+pub struct Components<N> {
+    pub labels: HashMap<N, usize>,
+    pub sizes: Vec<usize>,
+}
+
+/// Collects every cell reachable from `seed` by repeatedly stepping to
+/// `passable` neighbours. The seed itself must be passable to be included.
+pub fn flood_fill<N, I>(
+    seed: N,
+    mut neighbors: impl FnMut(N) -> I,
+    mut passable: impl FnMut(N) -> bool,
+) -> HashSet<N>
+where
+    N: Copy + Eq + Hash,
+    I: IntoIterator<Item = N>,
+{
+    let mut visited = HashSet::new();
+    if !passable(seed) {
+        return visited;
+    }
+
+    let mut next = VecDeque::from([seed]);
+    visited.insert(seed);
+    while let Some(cell) = next.pop_front() {
+        for n in neighbors(cell) {
+            if !visited.contains(&n) && passable(n) {
+                visited.insert(n);
+                next.push_back(n);
+            }
+        }
+    }
+
+    visited
+}
+
+/// Splits `cells` into components, where two neighbouring cells belong to the
+/// same component if `connected` holds for them. Components are numbered in
+/// the order their first cell appears in `cells`.
+pub fn label_components<N, I>(
+    cells: impl IntoIterator<Item = N>,
+    mut neighbors: impl FnMut(N) -> I,
+    mut connected: impl FnMut(N, N) -> bool,
+) -> Components<N>
+where
+    N: Copy + Eq + Hash,
+    I: IntoIterator<Item = N>,
+{
+    let mut components = Components {
+        labels: HashMap::new(),
+        sizes: Vec::new(),
+    };
+
+    for cell in cells {
+        if components.labels.contains_key(&cell) {
+            continue;
+        }
+
+        let label = components.sizes.len();
+        let region = flood_fill(
+            cell,
+            |n| {
+                neighbors(n)
+                    .into_iter()
+                    .filter(|m| connected(n, *m))
+                    .collect::<Vec<_>>()
+            },
+            |_| true,
+        );
+
+        components.sizes.push(region.len());
+        components
+            .labels
+            .extend(region.into_iter().map(|n| (n, label)));
+    }
+
+    components
+}
+
+/// Lists every `(inside, outside)` pair of neighbouring cells that straddle the
+/// edge of `region`. The length of the result is the region's perimeter (or
+/// surface area, for voxels).
+pub fn boundary<N, I>(region: &HashSet<N>, mut neighbors: impl FnMut(N) -> I) -> Vec<(N, N)>
+where
+    N: Copy + Eq + Hash,
+    I: IntoIterator<Item = N>,
+{
+    region
+        .iter()
+        .flat_map(|cell| {
+            neighbors(*cell)
+                .into_iter()
+                .filter(|n| !region.contains(n))
+                .map(|n| (*cell, n))
+                .collect::<Vec<_>>()
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn line_neighbors(n: i32) -> [i32; 2] {
+        [n - 1, n + 1]
+    }
+
+    #[test]
+    fn test_flood_fill() {
+        let filled = flood_fill(3, line_neighbors, |n| (0..10).contains(&n) && n != 5);
+        assert_eq!(filled, (0..5).collect());
+
+        assert!(flood_fill(5, line_neighbors, |n| n != 5).is_empty());
+    }
+
+    #[test]
+    fn test_label_components() {
+        let values = [1, 1, 2, 2, 2, 1];
+        let components = label_components(0..values.len() as i32, line_neighbors, |a, b| {
+            values.get(b as usize) == Some(&values[a as usize])
+        });
+
+        assert_eq!(components.sizes, vec![2, 3, 1]);
+        assert_eq!(components.labels[&4], 1);
+        assert_eq!(components.labels[&5], 2);
+    }
+
+    #[test]
+    fn test_boundary() {
+        let region: HashSet<i32> = (2..5).collect();
+        let mut edges = boundary(&region, line_neighbors);
+        edges.sort();
+        assert_eq!(edges, vec![(2, 1), (4, 5)]);
+    }
+}
@@ -0,0 +1,87 @@
+//! Splitting puzzle input into blank-line-separated sections.
+//!
+//! Many inputs are groups of lines separated by blank lines: batches of
+//! numbers, a drawing followed by instructions, pairs of packets. A line
+//! counts as blank if it holds nothing but whitespace, so indented blank lines
+//! and `\r\n` line endings split sections the same way an empty `\n` does.
+
+/// A run of non-blank lines, borrowed from the original text.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Section<'a> {
+    /// The 1-based line number of the section's first line.
+    pub line: usize,
+    /// The section's lines, without the line break after the last one.
+    /// Indentation and trailing spaces are kept as they were.
+    pub text: &'a str,
+}
+
+impl<'a> Section<'a> {
+    /// The section's lines, each with its 1-based line number in the
+    /// original text.
+    pub fn lines(&self) -> impl Iterator<Item = (usize, &'a str)> {
+        self.text.lines().zip(self.line..).map(|(l, n)| (n, l))
+    }
+}
+
+/// The sections of `text`, in order. Blank lines before the first section,
+/// between sections and after the last one are all skipped.
+pub fn sections(text: &str) -> impl Iterator<Item = Section<'_>> {
+    let mut lines = text.split_inclusive('\n').enumerate();
+    let mut offset = 0;
+    std::iter::from_fn(move || {
+        let mut section: Option<(usize, usize, usize)> = None;
+        for (n, raw) in lines.by_ref() {
+            let start = offset;
+            offset += raw.len();
+            let line = raw.trim_end_matches(['\n', '\r']);
+            if line.trim().is_empty() {
+                if section.is_some() {
+                    break;
+                }
+                continue;
+            }
+
+            // (first line number, start offset, end offset)
+            let end = start + line.len();
+            section = Some(match section {
+                None => (n + 1, start, end),
+                Some((first, from, _)) => (first, from, end),
+            });
+        }
+
+        section.map(|(line, from, to)| Section {
+            line,
+            text: &text[from..to],
+        })
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_sections() {
+        let text = "\n  \na\n  b\r\n   \r\n\n c \nd\n\n";
+        let found: Vec<Section> = sections(text).collect();
+        assert_eq!(
+            found,
+            vec![
+                Section {
+                    line: 3,
+                    text: "a\n  b"
+                },
+                Section {
+                    line: 7,
+                    text: " c \nd"
+                },
+            ]
+        );
+        assert_eq!(
+            found[1].lines().collect::<Vec<_>>(),
+            vec![(7, " c "), (8, "d")]
+        );
+        assert_eq!(sections("").count(), 0);
+        assert_eq!(sections("x").next().unwrap().text, "x");
+    }
+}